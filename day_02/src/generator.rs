@@ -0,0 +1,451 @@
+use crate::{PasswordPolicy, Segmentation};
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::{Rng, SeedableRng};
+use std::cmp;
+
+const USAGE: &str =
+    "usage: generate <policy, e.g. '1-3 a'> <occurrence-range|exactly-one-position> [outcome] [count] [seed]";
+
+const FILLER: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// The most policy units a generated password needs, keeping passwords for
+/// policies with large numbers from filling the memory.
+const MAX_LENGTH: usize = 1000;
+
+/// How many passwords are drawn before giving up on one whose units stay
+/// apart once segmented.
+const ATTEMPTS: usize = 1000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpretation {
+    OccurrenceRange,
+    ExactlyOnePosition,
+}
+impl Interpretation {
+    fn parse(input: &str) -> Result<Interpretation, String> {
+        match input {
+            "occurrence-range" => Ok(Interpretation::OccurrenceRange),
+            "exactly-one-position" => Ok(Interpretation::ExactlyOnePosition),
+            _ => Err(format!("unkown interpretation '{}'", input)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Interpretation::OccurrenceRange => "occurrence-range",
+            Interpretation::ExactlyOnePosition => "exactly-one-position",
+        }
+    }
+}
+
+/// How a generated password relates to its policy.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Outcome {
+    Satisfy,
+    /// Fewer occurrences than the policy minimum.
+    TooFew,
+    /// More occurrences than the policy maximum.
+    TooMany,
+    /// The policy char at neither position.
+    Neither,
+    /// The policy char at both positions.
+    Both,
+    /// The password ends before the last position.
+    PastEnd,
+}
+impl Outcome {
+    fn parse(input: &str) -> Result<Outcome, String> {
+        match input {
+            "satisfy" => Ok(Outcome::Satisfy),
+            "too-few" => Ok(Outcome::TooFew),
+            "too-many" => Ok(Outcome::TooMany),
+            "neither" => Ok(Outcome::Neither),
+            "both" => Ok(Outcome::Both),
+            "past-end" => Ok(Outcome::PastEnd),
+            _ => Err(format!("unkown outcome '{}'", input)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Satisfy => "satisfy",
+            Outcome::TooFew => "too-few",
+            Outcome::TooMany => "too-many",
+            Outcome::Neither => "neither",
+            Outcome::Both => "both",
+            Outcome::PastEnd => "past-end",
+        }
+    }
+}
+
+fn random_filler<'a, R: Rng>(rng: &mut R, filler: &[&'a str], length: usize) -> Vec<&'a str> {
+    (0..length).map(|_| *filler.choose(rng).unwrap()).collect()
+}
+
+fn impossible(policy: &PasswordPolicy, interpretation: Interpretation, outcome: Outcome) -> String {
+    format!(
+        "cannot generate a '{}' password for '{}' under {}",
+        outcome.name(),
+        policy,
+        interpretation.name()
+    )
+}
+
+fn too_long(policy: &PasswordPolicy) -> String {
+    format!(
+        "passwords for '{}' would be longer than {} units",
+        policy, MAX_LENGTH
+    )
+}
+
+/// Draws a password for `policy` giving `outcome` under `interpretation`.
+/// Under grapheme segmentation the policy unit may merge with its neighbours,
+/// e.g. a combining accent with the filler before it, so passwords not
+/// splitting back into the units they were made of are drawn again.
+pub fn generate_password<R: Rng>(
+    rng: &mut R,
+    policy: &PasswordPolicy,
+    segmentation: Segmentation,
+    interpretation: Interpretation,
+    outcome: Outcome,
+) -> Result<String, String> {
+    for _ in 0..ATTEMPTS {
+        let units = random_units(rng, policy, interpretation, outcome)?;
+        let password = units.concat();
+        if segmentation.split(&password) == units {
+            return Ok(password);
+        }
+    }
+    Err(format!(
+        "cannot generate a '{}' password for '{}' under {} whose {}s stay apart",
+        outcome.name(),
+        policy,
+        interpretation.name(),
+        segmentation.unit_name()
+    ))
+}
+
+fn random_units<'a, R: Rng>(
+    rng: &mut R,
+    policy: &'a PasswordPolicy,
+    interpretation: Interpretation,
+    outcome: Outcome,
+) -> Result<Vec<&'a str>, String> {
+    let unit = policy.unit.as_str();
+    let filler: Vec<&str> = (0..FILLER.len())
+        .map(|i| &FILLER[i..i + 1])
+        .filter(|&f| f != unit)
+        .collect();
+    let (n1, n2) = policy.numbers;
+
+    let units = match interpretation {
+        Interpretation::OccurrenceRange => {
+            let (min, max) = match outcome {
+                Outcome::Satisfy if n1 <= n2 => (n1, n2),
+                Outcome::TooFew if n1 > 0 => (0, n1 - 1),
+                Outcome::TooMany => match n2.checked_add(1) {
+                    Some(min) => (min, n2.saturating_add(3)),
+                    None => return Err(impossible(policy, interpretation, outcome)),
+                },
+                _ => return Err(impossible(policy, interpretation, outcome)),
+            };
+            if min > MAX_LENGTH {
+                return Err(too_long(policy));
+            }
+            let occurences = rng.gen_range(min..=cmp::min(max, MAX_LENGTH));
+            let length = cmp::max(occurences, 1) + rng.gen_range(0..=8);
+            let mut units = random_filler(rng, &filler, length);
+            for i in index::sample(rng, length, occurences) {
+                units[i] = unit;
+            }
+            units
+        }
+        Interpretation::ExactlyOnePosition => {
+            if n1 == 0 || n2 == 0 {
+                return Err(format!("positions of '{}' are 1-based", policy));
+            }
+            let last = cmp::max(n1, n2);
+            let length = match outcome {
+                Outcome::PastEnd if last > 1 => rng.gen_range(1..cmp::min(last, MAX_LENGTH)),
+                Outcome::Satisfy if n1 == n2 => {
+                    return Err(impossible(policy, interpretation, outcome))
+                }
+                Outcome::Satisfy | Outcome::Neither | Outcome::Both if last > MAX_LENGTH => {
+                    return Err(too_long(policy))
+                }
+                Outcome::Satisfy | Outcome::Neither | Outcome::Both => last + rng.gen_range(0..=5),
+                _ => return Err(impossible(policy, interpretation, outcome)),
+            };
+            let mut units = random_filler(rng, &filler, length);
+            for u in units.iter_mut() {
+                if rng.gen_ratio(1, 4) {
+                    *u = unit;
+                }
+            }
+            let (hit, miss) = if rng.gen() { (n1, n2) } else { (n2, n1) };
+            match outcome {
+                Outcome::Satisfy => {
+                    units[hit - 1] = unit;
+                    units[miss - 1] = random_filler(rng, &filler, 1)[0];
+                }
+                Outcome::Neither => {
+                    units[hit - 1] = random_filler(rng, &filler, 1)[0];
+                    units[miss - 1] = random_filler(rng, &filler, 1)[0];
+                }
+                Outcome::Both => {
+                    units[hit - 1] = unit;
+                    units[miss - 1] = unit;
+                }
+                _ => (),
+            }
+            units
+        }
+    };
+
+    Ok(units)
+}
+
+/// Prints `count` input lines for `policy`, and the number of them that
+/// should be counted as valid under `interpretation` on stderr.
+pub fn run(args: &[String], segmentation: Segmentation) -> Result<(), String> {
+    let policy = PasswordPolicy::parse(args.first().ok_or(USAGE)?, segmentation)?;
+    let interpretation = Interpretation::parse(args.get(1).ok_or(USAGE)?)?;
+    let outcome = args
+        .get(2)
+        .map_or(Ok(Outcome::Satisfy), |o| Outcome::parse(o))?;
+    let count: usize = args
+        .get(3)
+        .map_or(Ok(10), |c| c.parse())
+        .map_err(|e| format!("invalid count: {}", e))?;
+    let mut rng = match args.get(4) {
+        Some(seed) => {
+            StdRng::seed_from_u64(seed.parse().map_err(|e| format!("invalid seed: {}", e))?)
+        }
+        None => StdRng::from_entropy(),
+    };
+
+    for _ in 0..count {
+        let password = generate_password(&mut rng, &policy, segmentation, interpretation, outcome)?;
+        println!("{}: {}", policy, password);
+    }
+    eprintln!(
+        "expected {} count: {}",
+        interpretation.name(),
+        if outcome == Outcome::Satisfy {
+            count
+        } else {
+            0
+        }
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{ExactlyOnePosition, OccurrenceRange, Policy};
+    use crate::PasswordLine;
+
+    fn assert_generates(policy: &str, interpretation: Interpretation, outcome: Outcome) {
+        assert_generates_with(Segmentation::Scalars, policy, interpretation, outcome)
+    }
+
+    fn assert_generates_with(
+        segmentation: Segmentation,
+        policy: &str,
+        interpretation: Interpretation,
+        outcome: Outcome,
+    ) {
+        let mut rng = StdRng::seed_from_u64(2020);
+        let policy = PasswordPolicy::parse(policy, segmentation).unwrap();
+        for _ in 0..100 {
+            let password =
+                generate_password(&mut rng, &policy, segmentation, interpretation, outcome)
+                    .unwrap();
+            let line = format!("{}: {}", policy, password);
+            let line = PasswordLine::parse(1, &line, segmentation).unwrap();
+            let verdict = match interpretation {
+                Interpretation::OccurrenceRange => OccurrenceRange.evaluate(&line),
+                Interpretation::ExactlyOnePosition => ExactlyOnePosition.evaluate(&line),
+            };
+            assert_eq!(
+                verdict.passed,
+                outcome == Outcome::Satisfy,
+                "{}",
+                verdict.reason
+            );
+        }
+    }
+
+    #[test]
+    fn occurrence_range() {
+        assert_generates("1-3 a", Interpretation::OccurrenceRange, Outcome::Satisfy);
+        assert_generates("1-3 a", Interpretation::OccurrenceRange, Outcome::TooFew);
+        assert_generates("1-3 a", Interpretation::OccurrenceRange, Outcome::TooMany);
+        assert_generates("0-0 ü", Interpretation::OccurrenceRange, Outcome::Satisfy);
+    }
+
+    #[test]
+    fn exactly_one_position() {
+        assert_generates(
+            "1-3 a",
+            Interpretation::ExactlyOnePosition,
+            Outcome::Satisfy,
+        );
+        assert_generates(
+            "1-3 a",
+            Interpretation::ExactlyOnePosition,
+            Outcome::Neither,
+        );
+        assert_generates("1-3 a", Interpretation::ExactlyOnePosition, Outcome::Both);
+        assert_generates(
+            "1-3 a",
+            Interpretation::ExactlyOnePosition,
+            Outcome::PastEnd,
+        );
+    }
+
+    #[test]
+    fn graphemes() {
+        for &outcome in &[Outcome::Satisfy, Outcome::Neither, Outcome::Both] {
+            assert_generates_with(
+                Segmentation::Graphemes,
+                "1-3 e\u{301}",
+                Interpretation::ExactlyOnePosition,
+                outcome,
+            );
+        }
+        for &outcome in &[Outcome::Satisfy, Outcome::TooFew, Outcome::TooMany] {
+            assert_generates_with(
+                Segmentation::Graphemes,
+                "1-3 e\u{301}",
+                Interpretation::OccurrenceRange,
+                outcome,
+            );
+        }
+        // A lone combining accent merges with whatever is before it.
+        assert_generates_with(
+            Segmentation::Graphemes,
+            "1-2 \u{301}",
+            Interpretation::ExactlyOnePosition,
+            Outcome::Satisfy,
+        );
+        let mut rng = StdRng::seed_from_u64(2020);
+        let policy = PasswordPolicy::parse("2-3 \u{301}", Segmentation::Graphemes).unwrap();
+        assert!(generate_password(
+            &mut rng,
+            &policy,
+            Segmentation::Graphemes,
+            Interpretation::ExactlyOnePosition,
+            Outcome::Both
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn large_numbers() {
+        assert_generates(
+            "1-1000000000 a",
+            Interpretation::OccurrenceRange,
+            Outcome::Satisfy,
+        );
+        assert_generates(
+            "5000-6000 a",
+            Interpretation::OccurrenceRange,
+            Outcome::TooFew,
+        );
+        assert_generates(
+            "1-1000000000 a",
+            Interpretation::ExactlyOnePosition,
+            Outcome::PastEnd,
+        );
+
+        let mut rng = StdRng::seed_from_u64(2020);
+        let mut generate = |policy: &str, interpretation, outcome| {
+            let policy = PasswordPolicy::parse(policy, Segmentation::Scalars).unwrap();
+            generate_password(
+                &mut rng,
+                &policy,
+                Segmentation::Scalars,
+                interpretation,
+                outcome,
+            )
+            .map(|password| password.len())
+        };
+        assert_eq!(
+            generate(
+                "1000-1000 a",
+                Interpretation::OccurrenceRange,
+                Outcome::TooMany
+            ),
+            Err(String::from(
+                "passwords for '1000-1000 a' would be longer than 1000 units"
+            ))
+        );
+        assert!(generate(
+            "2000-3000 a",
+            Interpretation::OccurrenceRange,
+            Outcome::Satisfy
+        )
+        .is_err());
+        assert!(generate(
+            "1-1000000000 a",
+            Interpretation::ExactlyOnePosition,
+            Outcome::Satisfy
+        )
+        .is_err());
+        assert!(
+            generate("1-999 a", Interpretation::OccurrenceRange, Outcome::TooMany).unwrap()
+                <= MAX_LENGTH + 8
+        );
+    }
+
+    #[test]
+    fn impossible_outcomes() {
+        let mut rng = StdRng::seed_from_u64(2020);
+        let policy = PasswordPolicy::parse("0-3 a", Segmentation::Scalars).unwrap();
+        assert!(generate_password(
+            &mut rng,
+            &policy,
+            Segmentation::Scalars,
+            Interpretation::OccurrenceRange,
+            Outcome::TooFew
+        )
+        .is_err());
+        assert!(generate_password(
+            &mut rng,
+            &policy,
+            Segmentation::Scalars,
+            Interpretation::OccurrenceRange,
+            Outcome::Both
+        )
+        .is_err());
+        let policy =
+            PasswordPolicy::parse("0-18446744073709551615 a", Segmentation::Scalars).unwrap();
+        assert_eq!(
+            generate_password(
+                &mut rng,
+                &policy,
+                Segmentation::Scalars,
+                Interpretation::OccurrenceRange,
+                Outcome::TooMany
+            ),
+            Err(impossible(
+                &policy,
+                Interpretation::OccurrenceRange,
+                Outcome::TooMany
+            ))
+        );
+        let policy = PasswordPolicy::parse("2-2 a", Segmentation::Scalars).unwrap();
+        assert!(generate_password(
+            &mut rng,
+            &policy,
+            Segmentation::Scalars,
+            Interpretation::ExactlyOnePosition,
+            Outcome::Satisfy
+        )
+        .is_err());
+    }
+}
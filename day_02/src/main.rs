@@ -1,65 +1,407 @@
+use flate2::read::GzDecoder;
 use regex::Regex;
+use std::env;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
+use unicode_segmentation::UnicodeSegmentation;
+#[macro_use]
+extern crate lazy_static;
 
-struct PasswordPolicy {
+mod generator;
+mod policy;
+
+use policy::{ExactlyOnePosition, OccurrenceRange, Policy, Verdict};
+
+/// What a policy counts as one "character" of a password.
+#[derive(Copy, Clone)]
+pub enum Segmentation {
+    Scalars,
+    Graphemes,
+}
+impl Segmentation {
+    fn split<'a>(&self, string: &'a str) -> Vec<&'a str> {
+        match self {
+            Segmentation::Scalars => string
+                .char_indices()
+                .map(|(i, c)| &string[i..i + c.len_utf8()])
+                .collect(),
+            Segmentation::Graphemes => string.graphemes(true).collect(),
+        }
+    }
+
+    fn unit_name(&self) -> &'static str {
+        match self {
+            Segmentation::Scalars => "char",
+            Segmentation::Graphemes => "grapheme",
+        }
+    }
+}
+
+pub struct PasswordPolicy {
     numbers: (usize, usize),
-    char: char,
+    unit: String,
+}
+
+impl PasswordPolicy {
+    /// Parses the policy part of a line, e.g. `1-3 a`.
+    fn parse(policy: &str, segmentation: Segmentation) -> Result<PasswordPolicy, String> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(\d+)-(\d+)\s+(\S+)$").unwrap();
+        }
+        let caps = RE
+            .captures(policy)
+            .ok_or(format!("'{}' is not a policy like '1-3 a'", policy))?;
+        let parse_number = |i: usize| {
+            caps.get(i)
+                .unwrap()
+                .as_str()
+                .parse::<usize>()
+                .map_err(|e| e.to_string())
+        };
+        let unit = caps.get(3).unwrap().as_str();
+        if segmentation.split(unit).len() != 1 {
+            return Err(format!(
+                "policy character '{}' is not a single {}",
+                unit,
+                segmentation.unit_name()
+            ));
+        }
+
+        Ok(PasswordPolicy {
+            numbers: (parse_number(1)?, parse_number(2)?),
+            unit: String::from(unit),
+        })
+    }
+}
+
+impl fmt::Display for PasswordPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (n1, n2) = self.numbers;
+        write!(f, "{}-{} {}", n1, n2, self.unit)
+    }
 }
 
-struct PasswordLine {
+pub struct PasswordLine {
+    number: usize,
     policy: PasswordPolicy,
     password: String,
+    segmentation: Segmentation,
 }
 
-fn main() -> std::io::Result<()> {
-    let f = File::open("input.txt")?;
-    let f = BufReader::new(f);
+impl PasswordLine {
+    fn parse(
+        number: usize,
+        line: &str,
+        segmentation: Segmentation,
+    ) -> Result<PasswordLine, String> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(\d+-\d+\s+\S+?):\s+(\S+)$").unwrap();
+        }
+        let caps = RE
+            .captures(line)
+            .ok_or(format!("line {}: '{}' doesn't match", number, line))?;
 
-    let re = Regex::new(r"(\d+)-(\d+)\s+([a-z]):\s+([a-z]+)").unwrap();
+        Ok(PasswordLine {
+            number,
+            policy: PasswordPolicy::parse(caps.get(1).unwrap().as_str(), segmentation)
+                .map_err(|e| format!("line {}: {}", number, e))?,
+            password: String::from(caps.get(2).unwrap().as_str()),
+            segmentation,
+        })
+    }
+
+    fn units(&self) -> Vec<&str> {
+        self.segmentation.split(self.password.as_str())
+    }
+}
+
+fn status(verdict: &Verdict) -> &'static str {
+    if verdict.passed {
+        "pass"
+    } else {
+        "FAIL"
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+enum AuditFormat {
+    Table,
+    Csv,
+}
+impl AuditFormat {
+    fn parse(input: Option<&str>) -> Result<AuditFormat, String> {
+        match input {
+            None | Some("table") => Ok(AuditFormat::Table),
+            Some("csv") => Ok(AuditFormat::Csv),
+            Some(format) => Err(format!("unkown audit format '{}'", format)),
+        }
+    }
 
-    let password_lines: Vec<PasswordLine> = f
-        .lines()
-        .filter_map(Result::ok)
-        .map(|l| {
-            let caps = re.captures(l.as_str()).unwrap();
+    fn write_header<W: Write>(&self, mut out: W) -> io::Result<()> {
+        match self {
+            AuditFormat::Table => writeln!(
+                out,
+                "{:>5}  {:<8}  {:<24}  {:<4}  {:<32}  {:<4}  positional",
+                "line", "policy", "password", "occ", "occurrence-range", "pos"
+            ),
+            AuditFormat::Csv => writeln!(
+                out,
+                "line,policy,password,occurrence_range,occurrence_range_reason,positional,positional_reason"
+            ),
+        }
+    }
 
-            PasswordLine {
-                policy: PasswordPolicy {
-                    numbers: (
-                        caps.get(1).unwrap().as_str().parse::<usize>().unwrap(),
-                        caps.get(2).unwrap().as_str().parse::<usize>().unwrap(),
-                    ),
-                    char: caps.get(3).unwrap().as_str().chars().next().unwrap(),
-                },
-                password: String::from(caps.get(4).unwrap().as_str()),
+    fn write_row<W: Write>(&self, mut out: W, pl: &PasswordLine) -> io::Result<()> {
+        let occurrence = OccurrenceRange.evaluate(pl);
+        let positional = ExactlyOnePosition.evaluate(pl);
+        match self {
+            AuditFormat::Table => writeln!(
+                out,
+                "{:>5}  {:<8}  {:<24}  {:<4}  {:<32}  {:<4}  {}",
+                pl.number,
+                pl.policy.to_string(),
+                pl.password,
+                status(&occurrence),
+                occurrence.reason,
+                status(&positional),
+                positional.reason
+            ),
+            AuditFormat::Csv => writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                pl.number,
+                csv_field(&pl.policy.to_string()),
+                csv_field(&pl.password),
+                status(&occurrence),
+                csv_field(&occurrence.reason),
+                status(&positional),
+                csv_field(&positional.reason)
+            ),
+        }
+    }
+}
+
+/// Opens `path` as a line reader: `-` is stdin, and a `.gz` file is decompressed on the fly.
+fn open_input(path: &str) -> io::Result<Box<dyn BufRead>> {
+    let reader: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(path)?)
+    };
+    Ok(line_reader(reader, path.ends_with(".gz")))
+}
+
+/// Buffers `reader`, decompressing it first if it is `gzipped`.
+fn line_reader<R: Read + 'static>(reader: R, gzipped: bool) -> Box<dyn BufRead> {
+    if gzipped {
+        Box::new(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        Box::new(BufReader::new(reader))
+    }
+}
+
+/// Calls `f` with each password line of `reader` as it is read, reporting
+/// unparseable lines on stderr. Only one line is held in memory at a time.
+fn for_each_password_line<R: BufRead, F: FnMut(&PasswordLine)>(
+    mut reader: R,
+    segmentation: Segmentation,
+    mut f: F,
+) -> io::Result<()> {
+    let mut buffer = String::new();
+    let mut number = 0;
+    loop {
+        buffer.clear();
+        if reader.read_line(&mut buffer)? == 0 {
+            return Ok(());
+        }
+        number += 1;
+        let line = buffer.trim_end_matches(['\n', '\r']);
+        match PasswordLine::parse(number, line, segmentation) {
+            Ok(pl) => f(&pl),
+            Err(message) => eprintln!("{}", message),
+        }
+    }
+}
+
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    args.remove(i);
+    if i < args.len() {
+        Some(args.remove(i))
+    } else {
+        None
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let segmentation = if take_flag(&mut args, "--graphemes") {
+        Segmentation::Graphemes
+    } else {
+        Segmentation::Scalars
+    };
+    let input = take_option(&mut args, "--input").unwrap_or_else(|| String::from("input.txt"));
+
+    if args.first().map(String::as_str) == Some("generate") {
+        if let Err(message) = generator::run(&args[1..], segmentation) {
+            eprintln!("{}", message);
+        }
+        return Ok(());
+    }
+
+    match args.first().map(String::as_str) {
+        None => {
+            let mut result_part_1 = 0;
+            let mut result_part_2 = 0;
+            for_each_password_line(open_input(&input)?, segmentation, |pl| {
+                if OccurrenceRange.evaluate(pl).passed {
+                    result_part_1 += 1;
+                }
+                if ExactlyOnePosition.evaluate(pl).passed {
+                    result_part_2 += 1;
+                }
+            })?;
+            println!("{}", result_part_1);
+            println!("{}", result_part_2);
+        }
+        Some("audit") => match AuditFormat::parse(args.get(1).map(String::as_str)) {
+            Ok(format) => {
+                let stdout = io::stdout();
+                let mut out = stdout.lock();
+                format.write_header(&mut out)?;
+                let mut written = Ok(());
+                for_each_password_line(open_input(&input)?, segmentation, |pl| {
+                    if written.is_ok() {
+                        written = format.write_row(&mut out, pl);
+                    }
+                })?;
+                written?;
             }
+            Err(message) => eprintln!("{}", message),
+        },
+        Some("check") => match policy::parse_expression(&args[1..]) {
+            Ok(policy) => {
+                let mut result = 0;
+                for_each_password_line(open_input(&input)?, segmentation, |pl| {
+                    if policy.evaluate(pl).passed {
+                        result += 1;
+                    }
+                })?;
+                println!("{}", result);
+            }
+            Err(message) => eprintln!("{}", message),
+        },
+        Some(mode) => eprintln!("unkown mode '{}'", mode),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn lines<R: BufRead>(reader: R) -> Vec<(usize, String, String)> {
+        let mut lines = vec![];
+        for_each_password_line(reader, Segmentation::Scalars, |pl| {
+            lines.push((pl.number, pl.policy.to_string(), pl.password.clone()))
         })
-        .collect();
-
-    let result_part_1 = password_lines
-        .iter()
-        .filter(|pl| {
-            let char_occurences = pl.password.chars().filter(|&c| c == pl.policy.char).count();
-            let (min, max) = pl.policy.numbers;
-            min <= char_occurences && char_occurences <= max
-        })
-        .count();
-    println!("{}", result_part_1);
-
-    let result_part_2 = password_lines
-        .iter()
-        .filter(|pl| {
-            let (p1, p2) = pl.policy.numbers;
-            [p1, p2]
-                .iter()
-                .filter(|&p| pl.password.chars().nth(p - 1).unwrap() == pl.policy.char)
-                .count()
-                == 1
+        .unwrap();
+        lines
+    }
+
+    #[test]
+    fn streaming() {
+        let input = "1-3 a: abcde\r\nnot a policy\n2-9 c: ccccccccc\r\n\n1-3 b: cdefg";
+        assert_eq!(
+            lines(input.as_bytes()),
+            vec![
+                (1, String::from("1-3 a"), String::from("abcde")),
+                (3, String::from("2-9 c"), String::from("ccccccccc")),
+                (5, String::from("1-3 b"), String::from("cdefg")),
+            ]
+        );
+    }
+
+    fn audit(format: AuditFormat, input: &str) -> Vec<String> {
+        let mut out = vec![];
+        for_each_password_line(input.as_bytes(), Segmentation::Scalars, |pl| {
+            format.write_row(&mut out, pl).unwrap()
         })
-        .count();
-    println!("{}", result_part_2);
+        .unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
 
-    Ok(())
+    #[test]
+    fn audit_rows() {
+        let example = "1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc\n";
+        assert_eq!(
+            audit(AuditFormat::Table, example),
+            vec![
+                "    1  1-3 a     abcde                     pass  1 'a' found, within 1..=3         pass  'a' at position 1 only",
+                "    2  1-3 b     cdefg                     FAIL  0 'b' found, outside 1..=3        FAIL  'b' at neither position 1 nor 3",
+                "    3  2-9 c     ccccccccc                 pass  9 'c' found, within 2..=9         FAIL  'c' at both positions 2 and 9",
+            ]
+        );
+        assert_eq!(
+            audit(AuditFormat::Csv, example),
+            vec![
+                "1,1-3 a,abcde,pass,\"1 'a' found, within 1..=3\",pass,'a' at position 1 only",
+                "2,1-3 b,cdefg,FAIL,\"0 'b' found, outside 1..=3\",FAIL,'b' at neither position 1 nor 3",
+                "3,2-9 c,ccccccccc,pass,\"9 'c' found, within 2..=9\",FAIL,'c' at both positions 2 and 9",
+            ]
+        );
+        assert_eq!(
+            audit(AuditFormat::Csv, "1-1 ,: a\"b,\n"),
+            vec!["1,\"1-1 ,\",\"a\"\"b,\",pass,\"1 ',' found, within 1..=1\",FAIL,\"',' at neither position 1 nor 1\""]
+        );
+
+        let mut header = vec![];
+        AuditFormat::Csv.write_header(&mut header).unwrap();
+        assert_eq!(
+            String::from_utf8(header).unwrap(),
+            "line,policy,password,occurrence_range,occurrence_range_reason,positional,positional_reason\n"
+        );
+    }
+
+    #[test]
+    fn gzip() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"1-3 a: abcde\n1-3 b: cdefg\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(
+            lines(line_reader(io::Cursor::new(compressed), true)),
+            vec![
+                (1, String::from("1-3 a"), String::from("abcde")),
+                (2, String::from("1-3 b"), String::from("cdefg")),
+            ]
+        );
+        assert_eq!(lines(line_reader(&b"1-3 a: abcde\n"[..], false)).len(), 1);
+    }
 }
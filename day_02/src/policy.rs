@@ -0,0 +1,292 @@
+use crate::PasswordLine;
+
+pub struct Verdict {
+    pub passed: bool,
+    pub reason: String,
+}
+
+pub trait Policy {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict;
+}
+
+/// Part 1: the policy char must occur between `numbers.0` and `numbers.1` times.
+pub struct OccurrenceRange;
+impl Policy for OccurrenceRange {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let unit = line.policy.unit.as_str();
+        let char_occurences = line.units().into_iter().filter(|&u| u == unit).count();
+        let (min, max) = line.policy.numbers;
+        let passed = min <= char_occurences && char_occurences <= max;
+        Verdict {
+            passed,
+            reason: format!(
+                "{} '{}' found, {} {}..={}",
+                char_occurences,
+                unit,
+                if passed { "within" } else { "outside" },
+                min,
+                max
+            ),
+        }
+    }
+}
+
+/// Part 2: the policy char must be at exactly one of the two (1-based) positions.
+/// A position outside of the password fails the policy.
+pub struct ExactlyOnePosition;
+impl Policy for ExactlyOnePosition {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let (p1, p2) = line.policy.numbers;
+        let unit = line.policy.unit.as_str();
+        let units = line.units();
+        if let Some(&p) = [p1, p2].iter().find(|&&p| p == 0 || p > units.len()) {
+            return Verdict {
+                passed: false,
+                reason: format!(
+                    "position {} out of range 1..={} of the password",
+                    p,
+                    units.len()
+                ),
+            };
+        }
+        let matching: Vec<usize> = [p1, p2]
+            .iter()
+            .cloned()
+            .filter(|&p| units[p - 1] == unit)
+            .collect();
+        Verdict {
+            passed: matching.len() == 1,
+            reason: match matching.as_slice() {
+                [] => format!("'{}' at neither position {} nor {}", unit, p1, p2),
+                [p] => format!("'{}' at position {} only", unit, p),
+                _ => format!("'{}' at both positions {} and {}", unit, p1, p2),
+            },
+        }
+    }
+}
+
+pub struct DistinctChars(pub usize);
+impl Policy for DistinctChars {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let mut units = line.units();
+        units.sort_unstable();
+        units.dedup();
+        Verdict {
+            passed: units.len() >= self.0,
+            reason: format!("{} distinct chars, need {}", units.len(), self.0),
+        }
+    }
+}
+
+pub struct ForbiddenSubstring(pub String);
+impl Policy for ForbiddenSubstring {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        match line.password.find(self.0.as_str()) {
+            Some(i) => Verdict {
+                passed: false,
+                reason: format!(
+                    "contains '{}' at position {}",
+                    self.0,
+                    line.segmentation.split(&line.password[..i]).len() + 1
+                ),
+            },
+            None => Verdict {
+                passed: true,
+                reason: format!("does not contain '{}'", self.0),
+            },
+        }
+    }
+}
+
+pub struct MinLength(pub usize);
+impl Policy for MinLength {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let length = line.units().len();
+        Verdict {
+            passed: length >= self.0,
+            reason: format!("length {}, need at least {}", length, self.0),
+        }
+    }
+}
+
+/// No char may be repeated more than `self.0` times in a row.
+pub struct MaxRepeatRun(pub usize);
+impl Policy for MaxRepeatRun {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let mut longest: Option<(&str, usize)> = None;
+        let mut current: Option<(&str, usize)> = None;
+        for unit in line.units() {
+            current = match current {
+                Some((previous, run)) if previous == unit => Some((unit, run + 1)),
+                _ => Some((unit, 1)),
+            };
+            if current.map(|(_, run)| run) > longest.map(|(_, run)| run) {
+                longest = current;
+            }
+        }
+        match longest {
+            Some((unit, run)) => Verdict {
+                passed: run <= self.0,
+                reason: format!("'{}' repeated {} times, max {}", unit, run, self.0),
+            },
+            None => Verdict {
+                passed: true,
+                reason: String::from("empty password"),
+            },
+        }
+    }
+}
+
+pub struct And(pub Box<dyn Policy>, pub Box<dyn Policy>);
+impl Policy for And {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let left = self.0.evaluate(line);
+        let right = self.1.evaluate(line);
+        Verdict {
+            passed: left.passed && right.passed,
+            reason: format!("({}) and ({})", left.reason, right.reason),
+        }
+    }
+}
+
+pub struct Or(pub Box<dyn Policy>, pub Box<dyn Policy>);
+impl Policy for Or {
+    fn evaluate(&self, line: &PasswordLine) -> Verdict {
+        let left = self.0.evaluate(line);
+        let right = self.1.evaluate(line);
+        Verdict {
+            passed: left.passed || right.passed,
+            reason: format!("({}) or ({})", left.reason, right.reason),
+        }
+    }
+}
+
+fn parse_argument<T: std::str::FromStr>(name: &str, argument: Option<&str>) -> Result<T, String> {
+    argument
+        .ok_or(format!(
+            "policy '{}' needs an argument, e.g. {}=8",
+            name, name
+        ))?
+        .parse()
+        .map_err(|_| format!("invalid argument for policy '{}'", name))
+}
+
+/// Parses a single policy given as `name` or `name=argument`.
+pub fn parse_policy(token: &str) -> Result<Box<dyn Policy>, String> {
+    let mut split = token.splitn(2, '=');
+    let name = split.next().unwrap();
+    let argument = split.next();
+    match name {
+        "occurrence-range" => Ok(Box::new(OccurrenceRange)),
+        "exactly-one-position" => Ok(Box::new(ExactlyOnePosition)),
+        "distinct-chars" => Ok(Box::new(DistinctChars(parse_argument(name, argument)?))),
+        "forbidden" => Ok(Box::new(ForbiddenSubstring(parse_argument(
+            name, argument,
+        )?))),
+        "min-length" => Ok(Box::new(MinLength(parse_argument(name, argument)?))),
+        "max-repeat" => Ok(Box::new(MaxRepeatRun(parse_argument(name, argument)?))),
+        _ => Err(format!("unkown policy '{}'", name)),
+    }
+}
+
+/// Parses policies joined by `and` / `or`, `and` binding tighter than `or`.
+pub fn parse_expression(tokens: &[String]) -> Result<Box<dyn Policy>, String> {
+    let mut expression: Option<Box<dyn Policy>> = None;
+    for group in tokens.split(|t| t == "or") {
+        let mut conjunction: Option<Box<dyn Policy>> = None;
+        for operand in group.split(|t| t == "and") {
+            let policy = match operand {
+                [token] => parse_policy(token)?,
+                [] => return Err(String::from("missing policy around 'and' / 'or'")),
+                _ => return Err(format!("expected 'and' or 'or' after '{}'", operand[0])),
+            };
+            conjunction = Some(match conjunction {
+                Some(left) => Box::new(And(left, policy)),
+                None => policy,
+            });
+        }
+        let conjunction = conjunction.unwrap();
+        expression = Some(match expression {
+            Some(left) => Box::new(Or(left, conjunction)),
+            None => conjunction,
+        });
+    }
+    Ok(expression.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Segmentation;
+
+    fn check_with(segmentation: Segmentation, expression: &str, line: &str) -> bool {
+        let tokens: Vec<String> = expression.split(' ').map(String::from).collect();
+        parse_expression(&tokens)
+            .unwrap()
+            .evaluate(&PasswordLine::parse(1, line, segmentation).unwrap())
+            .passed
+    }
+
+    fn check(expression: &str, line: &str) -> bool {
+        check_with(Segmentation::Scalars, expression, line)
+    }
+
+    #[test]
+    fn example() {
+        assert!(check("occurrence-range", "1-3 a: abcde"));
+        assert!(check("occurrence-range", "2-9 c: ccccccccc"));
+        assert!(!check("occurrence-range", "1-3 b: cdefg"));
+        assert!(check("exactly-one-position", "1-3 a: abcde"));
+        assert!(!check("exactly-one-position", "1-3 b: cdefg"));
+        assert!(!check("exactly-one-position", "2-9 c: ccccccccc"));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // (false and false) or true
+        assert!(check(
+            "min-length=9 and forbidden=ccc or distinct-chars=1",
+            "2-9 c: ccccccccc"
+        ));
+        // false and (false or true) would fail
+        assert!(!check(
+            "forbidden=ccc and min-length=10 or distinct-chars=2",
+            "2-9 c: ccccccccc"
+        ));
+    }
+
+    #[test]
+    fn unicode_scalars() {
+        assert!(check("occurrence-range", "2-2 é: éaé"));
+        assert!(check("exactly-one-position", "1-2 ß: aßc"));
+        assert!(check("max-repeat=1", "1-1 🦀: 🦀a🦀"));
+    }
+
+    #[test]
+    fn grapheme_clusters() {
+        let line = "1-1 e\u{301}: xe\u{301}y";
+        assert!(PasswordLine::parse(1, line, Segmentation::Scalars).is_err());
+        assert!(check_with(
+            Segmentation::Graphemes,
+            "exactly-one-position",
+            "1-2 e\u{301}: xe\u{301}y"
+        ));
+        assert!(check_with(
+            Segmentation::Graphemes,
+            "occurrence-range",
+            line
+        ));
+    }
+
+    #[test]
+    fn out_of_range_positions_fail() {
+        assert!(!check("exactly-one-position", "1-30 a: abc"));
+        assert!(!check("exactly-one-position", "0-1 a: abc"));
+    }
+
+    #[test]
+    fn max_repeat_run() {
+        assert!(check("max-repeat=2", "1-3 a: aabaa"));
+        assert!(!check("max-repeat=2", "1-3 a: abaaab"));
+    }
+}
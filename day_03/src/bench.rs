@@ -0,0 +1,45 @@
+use crate::legend::Legend;
+use crate::Terrain;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+
+const WIDTH: usize = 31;
+/// One cell in `TREE_RATIO` is a tree.
+const TREE_RATIO: u32 = 4;
+
+fn generate(rows: usize) -> Terrain {
+    let mut rng = StdRng::seed_from_u64(2020);
+    let legend = Legend::default();
+    let tree = legend.tree().unwrap();
+    let mut terrain = Terrain::new(legend, WIDTH);
+    for row in 0..rows {
+        terrain.push_empty_row();
+        for column in 0..WIDTH {
+            if rng.gen_ratio(1, TREE_RATIO) {
+                terrain.set_kind(row, column, tree);
+            }
+        }
+    }
+    terrain
+}
+
+/// Times counting trees along the part 2 slopes on a generated map of `rows` rows.
+pub fn run(rows: usize) {
+    let start = Instant::now();
+    let terrain = generate(rows);
+    let bytes: usize = terrain.planes.iter().map(|p| p.len() * 8).sum();
+    println!(
+        "generated {} rows of {} cells ({} bytes of bits) in {:?}",
+        rows,
+        WIDTH,
+        bytes,
+        start.elapsed()
+    );
+
+    for &slope in &[(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)] {
+        let start = Instant::now();
+        let trees = terrain.trees_on_slope(slope);
+        println!("{:?} {} trees in {:?}", slope, trees, start.elapsed());
+    }
+}
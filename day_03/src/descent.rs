@@ -0,0 +1,184 @@
+use crate::{Slope, Terrain};
+use std::cmp;
+use std::collections::HashSet;
+
+/// Moves going `down` and right by anything from `min_right` to `max_right`.
+#[derive(Debug, PartialEq)]
+pub struct Moves {
+    pub min_right: usize,
+    pub max_right: usize,
+    pub down: usize,
+}
+
+/// Parses allowed moves given as `right,down`, where `right` may be an
+/// inclusive range: `0..=3,1` allows going down 1 and right 0 to 3.
+pub fn parse_moves(input: &str) -> Result<Moves, String> {
+    let error = || format!("'{}' is not a move like '3,1' or '0..=3,1'", input);
+    let mut split = input.splitn(2, ',');
+    let right = split.next().unwrap();
+    let down: usize = split
+        .next()
+        .ok_or_else(error)?
+        .parse()
+        .map_err(|_| error())?;
+    if down == 0 {
+        return Err(format!("move '{}' never goes down", input));
+    }
+    let (min_right, max_right) = match right.find("..=") {
+        Some(i) => (&right[..i], &right[i + 3..]),
+        None => (right, right),
+    };
+    let min_right: usize = min_right.parse().map_err(|_| error())?;
+    let max_right: usize = max_right.parse().map_err(|_| error())?;
+    if min_right > max_right {
+        return Err(format!("move '{}' has an empty range", input));
+    }
+    Ok(Moves {
+        min_right,
+        max_right,
+        down,
+    })
+}
+
+/// The distinct steps allowed by `moves` on a map `width` wide. Going right
+/// wraps around the map, so only `right % width` matters.
+fn steps(moves: &[Moves], width: usize) -> Vec<Slope> {
+    let mut seen: HashSet<Slope> = HashSet::new();
+    let mut steps = vec![];
+    for moves in moves {
+        let max_right = cmp::min(moves.max_right, moves.min_right.saturating_add(width - 1));
+        for right in moves.min_right..=max_right {
+            let step = (right % width, moves.down);
+            if seen.insert(step) {
+                steps.push(step);
+            }
+        }
+    }
+    steps
+}
+
+/// The fewest trees hit to reach a cell, and the previous column and move
+/// on that path.
+type Reached = (usize, Option<(usize, Slope)>);
+
+pub struct Descent {
+    pub trees: usize,
+    /// The `(row, column)` positions visited, columns counting from the left
+    /// of the first tile.
+    pub path: Vec<(usize, usize)>,
+}
+
+/// Finds the path from the top left corner to the bottom row hitting the
+/// fewest trees, each step being one of `moves`. Returns `None` when no
+/// combination of moves lands on the bottom row. The path goes right by the
+/// remainder of each move's `right` divided by the map width.
+pub fn least_trees(terrain: &Terrain, moves: &[Moves]) -> Option<Descent> {
+    let height = terrain.height;
+    let width = terrain.width;
+    if height == 0 || width == 0 {
+        return None;
+    }
+    let moves = steps(moves, width);
+    let trees_at = |row, column| terrain.is_tree(row, column) as usize;
+
+    let mut best: Vec<Vec<Option<Reached>>> = vec![vec![None; width]; height];
+    best[0][0] = Some((trees_at(0, 0), None));
+    for row in 0..height {
+        for column in 0..width {
+            let trees = match best[row][column] {
+                Some((trees, _)) => trees,
+                None => continue,
+            };
+            for &(right, down) in &moves {
+                let next_row = row + down;
+                if next_row >= height {
+                    continue;
+                }
+                let next_column = (column + right) % width;
+                let next_trees = trees + trees_at(next_row, next_column);
+                let next = &mut best[next_row][next_column];
+                if next.is_none_or(|(t, _)| next_trees < t) {
+                    *next = Some((next_trees, Some((column, (right, down)))));
+                }
+            }
+        }
+    }
+
+    let (mut column, trees) = best[height - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(column, b)| b.map(|(trees, _)| (column, trees)))
+        .min_by_key(|&(_, trees)| trees)?;
+
+    let mut steps = vec![];
+    let mut row = height - 1;
+    while let Some((_, Some((previous_column, step)))) = best[row][column] {
+        steps.push(step);
+        row -= step.1;
+        column = previous_column;
+    }
+    steps.reverse();
+
+    let mut position = (0, 0);
+    let mut path = vec![position];
+    for (right, down) in steps {
+        position = (position.0 + down, position.1 + right);
+        path.push(position);
+    }
+
+    Some(Descent { trees, path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legend::Legend;
+
+    #[test]
+    fn example() {
+        let terrain =
+            Terrain::parse(&include_bytes!("../example.txt")[..], Legend::default()).unwrap();
+
+        let descent = least_trees(&terrain, &[parse_moves("3,1").unwrap()]).unwrap();
+        assert_eq!(descent.trees, terrain.trees_on_slope((3, 1)));
+        assert_eq!(descent.path, terrain.path((3, 1)).unwrap());
+
+        let descent = least_trees(&terrain, &[parse_moves("0..=2,1").unwrap()]).unwrap();
+        assert_eq!(descent.trees, 0);
+        assert_eq!(descent.path.len(), 11);
+
+        assert!(least_trees(&terrain, &[parse_moves("1,3").unwrap()]).is_none());
+
+        // Going right by 14 is going right by 3 on a map 11 wide.
+        let descent = least_trees(&terrain, &[parse_moves("14,1").unwrap()]).unwrap();
+        assert_eq!(descent.trees, terrain.trees_on_slope((3, 1)));
+        assert_eq!(descent.path, terrain.path((3, 1)).unwrap());
+
+        let descent = least_trees(&terrain, &[parse_moves("0..=100000000000,1").unwrap()]).unwrap();
+        assert_eq!(descent.trees, 0);
+        let descent =
+            least_trees(&terrain, &[parse_moves("18446744073709551615,1").unwrap()]).unwrap();
+        assert_eq!(descent.path.len(), 11);
+    }
+
+    #[test]
+    fn moves() {
+        assert_eq!(steps(&[parse_moves("9..=25,2").unwrap()], 11).len(), 11);
+        assert_eq!(
+            steps(
+                &[
+                    parse_moves("1,1").unwrap(),
+                    parse_moves("12..=13,1").unwrap()
+                ],
+                11
+            ),
+            vec![(1, 1), (2, 1)]
+        );
+        assert_eq!(
+            parse_moves("3..=1,1"),
+            Err(String::from("move '3..=1,1' has an empty range"))
+        );
+        assert!(parse_moves("3,0").is_err());
+        assert!(parse_moves("3").is_err());
+    }
+}
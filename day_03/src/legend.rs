@@ -0,0 +1,92 @@
+use std::io::prelude::*;
+
+pub struct Kind {
+    pub symbol: char,
+    pub name: String,
+    pub cost: i64,
+}
+
+/// Which map characters are which kind of cell. Characters that are not
+/// in the legend are open snow, costing nothing.
+pub struct Legend {
+    pub kinds: Vec<Kind>,
+}
+impl Legend {
+    /// Parses one kind per line as `<symbol> <name> <cost>`, e.g. `R rock 5`.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Legend, String> {
+        let mut kinds: Vec<Kind> = vec![];
+        for (i, line) in reader.lines().map_while(Result::ok).enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let kind = match fields.as_slice() {
+                [] => continue,
+                [symbol, name, cost] if symbol.chars().count() == 1 => Kind {
+                    symbol: symbol.chars().next().unwrap(),
+                    name: String::from(*name),
+                    cost: cost
+                        .parse()
+                        .map_err(|e| format!("legend line {}: invalid cost: {}", i + 1, e))?,
+                },
+                _ => {
+                    return Err(format!(
+                        "legend line {}: '{}' is not like 'R rock 5'",
+                        i + 1,
+                        line
+                    ))
+                }
+            };
+            if kinds.iter().any(|k| k.symbol == kind.symbol) {
+                return Err(format!(
+                    "legend line {}: '{}' is already in the legend",
+                    i + 1,
+                    kind.symbol
+                ));
+            }
+            kinds.push(kind);
+        }
+        Ok(Legend { kinds })
+    }
+
+    pub fn kind_of(&self, symbol: char) -> Option<usize> {
+        self.kinds.iter().position(|k| k.symbol == symbol)
+    }
+
+    /// The kind counted as trees by the puzzle parts.
+    pub fn tree(&self) -> Option<usize> {
+        self.kinds.iter().position(|k| k.name == "tree")
+    }
+}
+
+impl Default for Legend {
+    fn default() -> Legend {
+        Legend {
+            kinds: vec![Kind {
+                symbol: '#',
+                name: String::from("tree"),
+                cost: 1,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let legend = Legend::parse(&include_bytes!("../legend.txt")[..]).unwrap();
+        let names: Vec<&str> = legend.kinds.iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(names, vec!["tree", "rock", "snowdrift", "lift"]);
+        assert_eq!(legend.kind_of('~'), Some(2));
+        assert_eq!(legend.kind_of('.'), None);
+        assert_eq!(legend.tree(), Some(0));
+        assert_eq!(legend.kinds[3].cost, -3);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Legend::parse(&b"R rock\n"[..]).is_err());
+        assert!(Legend::parse(&b"R rock five\n"[..]).is_err());
+        assert!(Legend::parse(&b"R rock 5\nR boulder 6\n"[..]).is_err());
+    }
+}
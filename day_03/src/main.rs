@@ -1,63 +1,441 @@
+use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 
+mod bench;
+mod descent;
+mod legend;
+mod render;
+
+use legend::Legend;
+use render::Rendering;
+
+#[derive(Copy, Clone)]
 enum Cell {
-    Tree,
+    /// An index in the terrain legend.
+    Kind(usize),
     Nothing,
 }
 
-fn main() -> std::io::Result<()> {
-    let f = File::open("input.txt")?;
-    let f = BufReader::new(f);
+/// A `(right, down)` step.
+type Slope = (usize, usize);
 
-    let terrain: Vec<Vec<Cell>> = f
-        .lines()
-        .filter_map(Result::ok)
-        .map(|l| {
-            l.chars()
-                .map(|c| match c {
-                    '#' => Cell::Tree,
-                    _ => Cell::Nothing,
-                })
-                .collect()
-        })
-        .collect();
+/// The map packed one bit per cell and per legend kind. Each row starts on
+/// a new word so that a cell is found without scanning its row.
+struct Terrain {
+    legend: Legend,
+    /// One bitset per legend kind, set where a cell is of that kind.
+    planes: Vec<Vec<u64>>,
+    width: usize,
+    height: usize,
+}
+impl Terrain {
+    fn new(legend: Legend, width: usize) -> Terrain {
+        Terrain {
+            planes: legend.kinds.iter().map(|_| vec![]).collect(),
+            legend,
+            width,
+            height: 0,
+        }
+    }
 
-    let result_part_1 = terrain
-        .iter()
-        .enumerate()
-        .filter(|(i, l)| match l.iter().nth(i * 3 % l.len()).unwrap() {
-            Cell::Nothing => false,
-            Cell::Tree => true,
-        })
-        .count();
-    println!("{}", result_part_1);
+    fn words_per_row(&self) -> usize {
+        self.width.div_ceil(64)
+    }
+
+    fn push_empty_row(&mut self) {
+        let words_per_row = self.words_per_row();
+        for plane in self.planes.iter_mut() {
+            plane.resize(plane.len() + words_per_row, 0);
+        }
+        self.height += 1;
+    }
+
+    fn set_kind(&mut self, row: usize, column: usize, kind: usize) {
+        let i = row * self.words_per_row() + column / 64;
+        self.planes[kind][i] |= 1 << (column % 64);
+    }
+
+    fn parse<R: BufRead>(reader: R, legend: Legend) -> Result<Terrain, String> {
+        let mut terrain = Terrain::new(legend, 0);
+        for (row, line) in reader.lines().map_while(Result::ok).enumerate() {
+            let width = line.chars().count();
+            if row == 0 {
+                terrain.width = width;
+            }
+            if width == 0 || width != terrain.width {
+                return Err(format!(
+                    "line {} is {} cells wide, expected {}",
+                    row + 1,
+                    width,
+                    terrain.width
+                ));
+            }
+            terrain.push_empty_row();
+            for (column, c) in line.chars().enumerate() {
+                if let Some(kind) = terrain.legend.kind_of(c) {
+                    terrain.set_kind(row, column, kind);
+                }
+            }
+        }
+        Ok(terrain)
+    }
+
+    /// Whether the cell at `row`, `column` is of `kind`, the row pattern
+    /// repeating to the right.
+    fn has_kind(&self, row: usize, column: usize, kind: usize) -> bool {
+        let column = column % self.width;
+        self.planes[kind][row * self.words_per_row() + column / 64] >> (column % 64) & 1 == 1
+    }
+
+    fn is_tree(&self, row: usize, column: usize) -> bool {
+        self.legend
+            .tree()
+            .is_some_and(|tree| self.has_kind(row, column, tree))
+    }
 
-    let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+    /// The cell at `row`, `column`, the row pattern repeating to the right.
+    fn cell(&self, row: usize, column: usize) -> Cell {
+        match (0..self.planes.len()).find(|&kind| self.has_kind(row, column, kind)) {
+            Some(kind) => Cell::Kind(kind),
+            None => Cell::Nothing,
+        }
+    }
 
-    let result_part_2 = slopes
+    /// The `(row, column)` positions visited on `slope`, columns counting
+    /// from the left of the first tile.
+    fn path(&self, (right, down): Slope) -> Result<Vec<(usize, usize)>, String> {
+        (0..self.height)
+            .step_by(down)
+            .enumerate()
+            .map(|(i, row)| match i.checked_mul(right) {
+                Some(column) => Ok((row, column)),
+                None => Err(format!("slope {:?} goes too far right", (right, down))),
+            })
+            .collect()
+    }
+
+    /// How many cells of each legend kind are visited on `slope`.
+    fn kinds_on_slope(&self, (right, down): Slope) -> Vec<usize> {
+        let words_per_row = self.words_per_row();
+        let right = right % self.width.max(1);
+        let mut column = 0;
+        let mut counts = vec![0; self.planes.len()];
+        for row in (0..self.height).step_by(down) {
+            let i = row * words_per_row + column / 64;
+            for (count, plane) in counts.iter_mut().zip(&self.planes) {
+                *count += (plane[i] >> (column % 64) & 1) as usize;
+            }
+            column += right;
+            if column >= self.width {
+                column -= self.width;
+            }
+        }
+        counts
+    }
+
+    fn trees_on_slope(&self, slope: Slope) -> usize {
+        match self.legend.tree() {
+            Some(tree) => self.kinds_on_slope(slope)[tree],
+            None => 0,
+        }
+    }
+}
+
+fn parse_slope(input: &str) -> Result<Slope, String> {
+    let mut split = input.splitn(2, ',');
+    let right = split.next().unwrap().parse();
+    let down = split.next().map(str::parse);
+    match (right, down) {
+        (Ok(_), Some(Ok(0))) => Err(format!("slope '{}' never goes down", input)),
+        (Ok(right), Some(Ok(down))) => Ok((right, down)),
+        _ => Err(format!("'{}' is not a slope like '3,1'", input)),
+    }
+}
+
+fn print_slopes_product(terrain: &Terrain, slopes: &[Slope]) {
+    let result = slopes
         .iter()
-        .map(|slope| {
-            let (right, down) = slope;
-            let tree_count = terrain
-                .iter()
-                .enumerate()
-                .filter(|(i, l)| {
-                    (i % down == 0)
-                        && match l.iter().nth((i / down) * right % l.len()).unwrap() {
-                            Cell::Nothing => false,
-                            Cell::Tree => true,
-                        }
-                })
-                .count();
+        .map(|&slope| {
+            let tree_count = terrain.trees_on_slope(slope);
 
             println!("{:?} {}", slope, tree_count);
 
             tree_count
         })
-        .fold(1, |acc, trees_count| acc * trees_count);
-    println!("{}", result_part_2);
+        .product::<usize>();
+    println!("{}", result);
+}
+
+/// The slopes hitting the fewest trees, and the ones hitting the most, each
+/// with that number of trees.
+type SlopeSearch = ((usize, Vec<Slope>), (usize, Vec<Slope>));
+
+/// Evaluates every slope up to `max_right` and `max_down`, and finds the ones
+/// hitting the fewest and the most trees. Slopes are not reduced: `(2, 2)`
+/// only visits every other cell of `(1, 1)`, so both are evaluated.
+fn search_slopes(terrain: &Terrain, max_right: usize, max_down: usize) -> Option<SlopeSearch> {
+    let results: Vec<(Slope, usize)> = (1..=max_down)
+        .flat_map(|down| (0..=max_right).map(move |right| (right, down)))
+        .map(|slope| (slope, terrain.trees_on_slope(slope)))
+        .collect();
+
+    let hitting = |trees: usize| -> (usize, Vec<Slope>) {
+        let slopes = results
+            .iter()
+            .filter(|r| r.1 == trees)
+            .map(|r| r.0)
+            .collect();
+        (trees, slopes)
+    };
+    let fewest = results.iter().map(|r| r.1).min()?;
+    let most = results.iter().map(|r| r.1).max()?;
+    Some((hitting(fewest), hitting(most)))
+}
+
+fn print_slopes_cost(terrain: &Terrain, slopes: &[Slope]) {
+    for &slope in slopes {
+        let counts = terrain.kinds_on_slope(slope);
+        let total: i64 = terrain
+            .legend
+            .kinds
+            .iter()
+            .zip(&counts)
+            .map(|(kind, &count)| kind.cost * count as i64)
+            .sum();
+        let breakdown: Vec<String> = terrain
+            .legend
+            .kinds
+            .iter()
+            .zip(&counts)
+            .map(|(kind, count)| format!("{} {} x {}", kind.name, count, kind.cost))
+            .collect();
+        println!("{:?} {}: {}", slope, total, breakdown.join(", "));
+    }
+}
+
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    args.remove(i);
+    if i < args.len() {
+        Some(args.remove(i))
+    } else {
+        None
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let output = take_option(&mut args, "--output");
+    let legend_path = take_option(&mut args, "--legend");
+
+    if args.first().map(String::as_str) == Some("bench") {
+        match args.get(1).map_or(Ok(5_000_000), |r| r.parse()) {
+            Ok(rows) => bench::run(rows),
+            Err(e) => eprintln!("invalid number of rows: {}", e),
+        }
+        return Ok(());
+    }
+
+    let legend = match legend_path {
+        Some(path) => Legend::parse(BufReader::new(File::open(path)?)),
+        None => Ok(Legend::default()),
+    };
+
+    let f = File::open("input.txt")?;
+    let f = BufReader::new(f);
+
+    let terrain = match legend.and_then(|legend| Terrain::parse(f, legend)) {
+        Ok(terrain) => terrain,
+        Err(message) => {
+            eprintln!("{}", message);
+            return Ok(());
+        }
+    };
+
+    match args.first().map(String::as_str) {
+        None => {
+            let result_part_1 = terrain.trees_on_slope((3, 1));
+            println!("{}", result_part_1);
+
+            let slopes = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+            print_slopes_product(&terrain, &slopes);
+        }
+        Some("slopes") => {
+            match args[1..]
+                .iter()
+                .map(|s| parse_slope(s))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(slopes) if !slopes.is_empty() => print_slopes_product(&terrain, &slopes),
+                Ok(_) => eprintln!("usage: slopes <right,down>..."),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+        Some("cost") => {
+            match args[1..]
+                .iter()
+                .map(|s| parse_slope(s))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(slopes) if slopes.is_empty() => {
+                    print_slopes_cost(&terrain, &[(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)])
+                }
+                Ok(slopes) => print_slopes_cost(&terrain, &slopes),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+        Some("search") => {
+            let bound = |i: usize| {
+                args.get(i)
+                    .map_or(Ok(10), |b| b.parse::<usize>())
+                    .map_err(|e| format!("invalid bound: {}", e))
+            };
+            match (bound(1), bound(2)) {
+                (Ok(max_right), Ok(max_down)) => {
+                    match search_slopes(&terrain, max_right, max_down) {
+                        Some(((fewest, fewest_slopes), (most, most_slopes))) => {
+                            println!("fewest trees: {} {:?}", fewest, fewest_slopes);
+                            println!("most trees: {} {:?}", most, most_slopes);
+                        }
+                        None => eprintln!("no slopes to search"),
+                    }
+                }
+                (Err(message), _) | (_, Err(message)) => eprintln!("{}", message),
+            }
+        }
+        Some("render") => {
+            match args[1..]
+                .iter()
+                .map(|s| parse_slope(s))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(slopes) => {
+                    let slopes = if slopes.is_empty() {
+                        vec![(3, 1)]
+                    } else {
+                        slopes
+                    };
+                    let rendering = slopes
+                        .iter()
+                        .map(|&s| terrain.path(s))
+                        .collect::<Result<Vec<_>, _>>()
+                        .and_then(|paths| Rendering::new(&terrain, &paths));
+                    match rendering {
+                        Ok(rendering) => {
+                            let labels: Vec<String> =
+                                slopes.iter().map(|s| format!("{:?}", s)).collect();
+                            eprintln!("{}", render::legend(&labels));
+                            rendering.save(output.as_deref())?;
+                        }
+                        Err(message) => eprintln!("{}", message),
+                    }
+                }
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+        Some("least-trees") => {
+            match args[1..]
+                .iter()
+                .map(|s| descent::parse_moves(s))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(moves) if !moves.is_empty() => match descent::least_trees(&terrain, &moves) {
+                    Some(descent) => {
+                        println!("{}", descent.trees);
+                        let path: Vec<String> = descent
+                            .path
+                            .iter()
+                            .map(|position| format!("{:?}", position))
+                            .collect();
+                        println!("{}", path.join(" "));
+                        if output.is_some() {
+                            match Rendering::new(&terrain, &[descent.path]) {
+                                Ok(rendering) => rendering.save(output.as_deref())?,
+                                Err(message) => eprintln!("{}", message),
+                            }
+                        }
+                    }
+                    None => eprintln!("no path reaches the bottom row"),
+                },
+                Ok(_) => eprintln!("usage: least-trees <right,down | min..=max,down>..."),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+        Some(mode) => eprintln!("unkown mode '{}'", mode),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let terrain =
+            Terrain::parse(&include_bytes!("../example.txt")[..], Legend::default()).unwrap();
+        let trees: Vec<usize> = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)]
+            .iter()
+            .map(|&slope| terrain.trees_on_slope(slope))
+            .collect();
+        assert_eq!(trees, vec![2, 7, 3, 4, 2]);
+    }
+
+    #[test]
+    fn search() {
+        let terrain =
+            Terrain::parse(&include_bytes!("../example.txt")[..], Legend::default()).unwrap();
+        assert_eq!(
+            search_slopes(&terrain, 3, 1),
+            Some(((1, vec![(2, 1)]), (7, vec![(3, 1)])))
+        );
+        assert_eq!(search_slopes(&terrain, 3, 0), None);
+    }
+
+    #[test]
+    fn slopes() {
+        assert_eq!(parse_slope("3,1"), Ok((3, 1)));
+        assert_eq!(
+            parse_slope("3,0"),
+            Err(String::from("slope '3,0' never goes down"))
+        );
+        assert_eq!(
+            parse_slope("3"),
+            Err(String::from("'3' is not a slope like '3,1'"))
+        );
+        assert!(parse_slope("3,-1").is_err());
+        assert!(parse_slope("x,1").is_err());
+    }
+
+    #[test]
+    fn rows_wider_than_a_word() {
+        let line: String = (0..100)
+            .map(|i| if i % 3 == 0 { '#' } else { '.' })
+            .collect();
+        let input = format!("{}\n{}\n{}\n", line, line, line);
+        let terrain = Terrain::parse(input.as_bytes(), Legend::default()).unwrap();
+        for column in 0..300 {
+            assert_eq!(terrain.is_tree(1, column), column % 100 % 3 == 0);
+        }
+        assert_eq!(terrain.trees_on_slope((99, 1)), 2);
+    }
+
+    #[test]
+    fn kinds_on_slope() {
+        let legend = Legend::parse(&include_bytes!("../legend.txt")[..]).unwrap();
+        let terrain = Terrain::parse(&b"R...\n.~#.\n..L.\n#..#\n"[..], legend).unwrap();
+        // Visits R, ~, L and #.
+        assert_eq!(terrain.kinds_on_slope((1, 1)), vec![1, 1, 1, 1]);
+        // Visits R, # and then wraps around to two open cells.
+        assert_eq!(terrain.kinds_on_slope((2, 1)), vec![1, 1, 0, 0]);
+        assert_eq!(terrain.trees_on_slope((2, 1)), 1);
+    }
+
+    #[test]
+    fn ragged_rows() {
+        assert!(Terrain::parse(&b"..#\n.#\n"[..], Legend::default()).is_err());
+    }
+}
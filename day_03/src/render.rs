@@ -0,0 +1,277 @@
+use crate::{Cell, Terrain};
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// Open / tree hit markers of each path, cycled through when there are more paths.
+const MARKERS: [(char, char); 6] = [
+    ('O', 'X'),
+    ('o', 'x'),
+    ('U', 'V'),
+    ('u', 'v'),
+    ('S', 'T'),
+    ('s', 't'),
+];
+/// Marker of a cell traversed by several paths.
+const OVERLAP: char = '*';
+
+/// Open / tree hit colours of each path in images.
+const COLORS: [([u8; 3], [u8; 3]); 6] = [
+    ([120, 170, 255], [220, 30, 30]),
+    ([255, 200, 60], [150, 60, 0]),
+    ([190, 110, 255], [90, 0, 140]),
+    ([60, 220, 220], [0, 100, 100]),
+    ([255, 120, 200], [150, 0, 80]),
+    ([170, 255, 90], [60, 120, 0]),
+];
+const OPEN_COLOR: [u8; 3] = [250, 250, 250];
+const TREE_COLOR: [u8; 3] = [30, 110, 50];
+/// Colours of the legend kinds other than trees.
+const KIND_COLORS: [[u8; 3]; 4] = [
+    [130, 130, 130],
+    [190, 215, 255],
+    [240, 200, 110],
+    [150, 110, 80],
+];
+const OVERLAP_COLOR: [u8; 3] = [0, 0, 0];
+/// Side of a cell in image pixels.
+const SCALE: usize = 4;
+/// The most cells rendered, keeping paths far to the right from filling the
+/// memory with repeated tiles.
+const MAX_CELLS: usize = 10_000_000;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mark {
+    Open,
+    /// An index in the terrain legend.
+    Kind(usize),
+    Path {
+        index: usize,
+        hit: bool,
+    },
+    Overlap,
+}
+
+pub struct Rendering {
+    grid: Vec<Vec<Mark>>,
+    symbols: Vec<char>,
+    colors: Vec<[u8; 3]>,
+}
+impl Rendering {
+    /// Marks the `(row, column)` positions of each path, columns counting
+    /// from the left of the first tile. The map is repeated to the right as
+    /// many whole tiles as the paths wandered through, as long as that makes
+    /// at most `MAX_CELLS` cells.
+    pub fn new(terrain: &Terrain, paths: &[Vec<(usize, usize)>]) -> Result<Rendering, String> {
+        let tile_width = terrain.width;
+        let max_column = paths.iter().flatten().map(|p| p.1).max().unwrap_or(0);
+        let width = match max_column.checked_div(tile_width) {
+            Some(tiles) => (tiles + 1).checked_mul(tile_width),
+            None => Some(0),
+        };
+        let cells = width.and_then(|width| width.checked_mul(terrain.height));
+        let width = match (width, cells) {
+            (Some(width), Some(cells)) if cells <= MAX_CELLS => width,
+            _ => {
+                return Err(format!(
+                    "the paths go too far right to render, at most {} cells are rendered",
+                    MAX_CELLS
+                ))
+            }
+        };
+
+        let mut grid: Vec<Vec<Mark>> = (0..terrain.height)
+            .map(|row| {
+                (0..width)
+                    .map(|column| match terrain.cell(row, column) {
+                        Cell::Kind(kind) => Mark::Kind(kind),
+                        Cell::Nothing => Mark::Open,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (index, path) in paths.iter().enumerate() {
+            for &(row, column) in path {
+                let mark = &mut grid[row][column];
+                *mark = match *mark {
+                    Mark::Open => Mark::Path { index, hit: false },
+                    Mark::Kind(kind) => Mark::Path {
+                        index,
+                        hit: terrain.legend.tree() == Some(kind),
+                    },
+                    Mark::Path { index: other, .. } if other == index => *mark,
+                    _ => Mark::Overlap,
+                }
+            }
+        }
+
+        let tree = terrain.legend.tree();
+        Ok(Rendering {
+            grid,
+            symbols: terrain.legend.kinds.iter().map(|k| k.symbol).collect(),
+            colors: (0..terrain.legend.kinds.len())
+                .map(|kind| {
+                    if Some(kind) == tree {
+                        TREE_COLOR
+                    } else {
+                        KIND_COLORS[kind % KIND_COLORS.len()]
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    fn width(&self) -> usize {
+        self.grid.first().map_or(0, Vec::len)
+    }
+
+    pub fn write_text<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for row in &self.grid {
+            let line: String = row
+                .iter()
+                .map(|mark| match *mark {
+                    Mark::Open => '.',
+                    Mark::Kind(kind) => self.symbols[kind],
+                    Mark::Path { index, hit } => {
+                        let (open, tree) = MARKERS[index % MARKERS.len()];
+                        if hit {
+                            tree
+                        } else {
+                            open
+                        }
+                    }
+                    Mark::Overlap => OVERLAP,
+                })
+                .collect();
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    fn pixels(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.grid.len() * self.width() * SCALE * SCALE * 3);
+        for row in &self.grid {
+            let colors: Vec<[u8; 3]> = row
+                .iter()
+                .map(|mark| match *mark {
+                    Mark::Open => OPEN_COLOR,
+                    Mark::Kind(kind) => self.colors[kind],
+                    Mark::Path { index, hit } => {
+                        let (open, tree) = COLORS[index % COLORS.len()];
+                        if hit {
+                            tree
+                        } else {
+                            open
+                        }
+                    }
+                    Mark::Overlap => OVERLAP_COLOR,
+                })
+                .collect();
+            for _ in 0..SCALE {
+                for color in &colors {
+                    for _ in 0..SCALE {
+                        pixels.extend_from_slice(color);
+                    }
+                }
+            }
+        }
+        pixels
+    }
+
+    pub fn write_ppm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(
+            out,
+            "P6\n{} {}\n255\n",
+            self.width() * SCALE,
+            self.grid.len() * SCALE
+        )?;
+        out.write_all(&self.pixels())
+    }
+
+    pub fn write_png<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(
+            out,
+            (self.width() * SCALE) as u32,
+            (self.grid.len() * SCALE) as u32,
+        );
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels())?;
+        Ok(())
+    }
+
+    /// Writes to stdout as text, or to `path` as text, `.ppm` or `.png`
+    /// depending on its extension.
+    pub fn save(&self, path: Option<&str>) -> io::Result<()> {
+        match path {
+            None => self.write_text(io::stdout().lock()),
+            Some(path) => {
+                let out = BufWriter::new(File::create(path)?);
+                if path.ends_with(".ppm") {
+                    self.write_ppm(out)
+                } else if path.ends_with(".png") {
+                    self.write_png(out)
+                } else {
+                    self.write_text(out)
+                }
+            }
+        }
+    }
+}
+
+/// Describes which markers stand for which of the `labels` paths.
+pub fn legend(labels: &[String]) -> String {
+    let mut lines: Vec<String> = labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| {
+            let (open, tree) = MARKERS[index % MARKERS.len()];
+            format!("{}: {} open, {} tree", label, open, tree)
+        })
+        .collect();
+    if labels.len() > 1 {
+        lines.push(format!("{}: several paths", OVERLAP));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legend::Legend;
+
+    #[test]
+    fn example() {
+        let terrain =
+            Terrain::parse(&include_bytes!("../example.txt")[..], Legend::default()).unwrap();
+        let paths = vec![terrain.path((3, 1)).unwrap()];
+        let mut text = vec![];
+        Rendering::new(&terrain, &paths)
+            .unwrap()
+            .write_text(&mut text)
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "O.##.........##.........##.......");
+        assert_eq!(lines[1].len(), 33);
+        assert_eq!(lines[1], "#..O#...#..#...#...#..#...#...#..");
+        assert_eq!(lines[10], ".#..#...#.#.#..#...#.#.#..#...X.#");
+        assert_eq!(text.matches('X').count(), 7);
+    }
+
+    #[test]
+    fn too_large() {
+        let terrain =
+            Terrain::parse(&include_bytes!("../example.txt")[..], Legend::default()).unwrap();
+        let paths = vec![terrain.path((10_000_000, 1)).unwrap()];
+        assert!(Rendering::new(&terrain, &paths).is_err());
+        assert!(terrain.path((usize::MAX, 1)).is_err());
+        let paths = vec![vec![(0, 0), (1, usize::MAX)]];
+        assert!(Rendering::new(&terrain, &paths).is_err());
+    }
+}
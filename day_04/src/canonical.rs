@@ -0,0 +1,129 @@
+use crate::schema::Schema;
+use crate::Record;
+use std::io;
+use std::io::prelude::*;
+
+/// The tokens of `record` in canonical order: the schema fields in schema
+/// order, then the unknown fields in input order. Repeated fields stay
+/// together. Records with malformed tokens cannot be reordered safely, so
+/// their tokens are kept in input order and `Err` is returned.
+pub fn tokens(schema: &Schema, record: &Record) -> Result<Vec<String>, Vec<String>> {
+    if !record.malformed.is_empty() {
+        let mut tokens: Vec<_> = record
+            .fields
+            .iter()
+            .map(|f| (f.position, format!("{}:{}", f.key, f.value)))
+            .chain(record.malformed.iter().map(|(t, p)| (*p, t.clone())))
+            .collect();
+        tokens.sort_by_key(|(position, _)| (position.line, position.column));
+        return Err(tokens.into_iter().map(|(_, token)| token).collect());
+    }
+
+    let mut fields: Vec<_> = record.fields.iter().collect();
+    fields.sort_by_key(|field| {
+        schema
+            .fields
+            .iter()
+            .position(|f| f.key == field.key)
+            .unwrap_or(schema.fields.len())
+    });
+    Ok(fields
+        .iter()
+        .map(|f| format!("{}:{}", f.key, f.value))
+        .collect())
+}
+
+/// Joins `tokens` with spaces into lines of at most `width` characters, a
+/// longer token having its own line.
+pub fn wrap(tokens: &[String], width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.chars().count() + 1 + token.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Writes `records` in canonical layout, one paragraph per record, skipping
+/// empty records. Records that could not be parsed are written as they
+/// were and reported on stderr.
+pub fn run<I, W>(schema: &Schema, records: I, width: usize, mut out: W) -> io::Result<()>
+where
+    I: Iterator<Item = Record>,
+    W: Write,
+{
+    let mut first = true;
+    for record in records {
+        let tokens = match tokens(schema, &record) {
+            Ok(tokens) => tokens,
+            Err(tokens) => {
+                let malformed: Vec<String> = record
+                    .malformed
+                    .iter()
+                    .map(|(token, position)| format!("'{}' at {}", token, position))
+                    .collect();
+                eprintln!(
+                    "line {}: could not parse {}",
+                    record.line,
+                    malformed.join(", ")
+                );
+                tokens
+            }
+        };
+        if tokens.is_empty() {
+            continue;
+        }
+        if !first {
+            writeln!(out)?;
+        }
+        first = false;
+        for line in wrap(&tokens, width) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecordStream;
+
+    fn format(input: &[u8], width: usize) -> String {
+        let mut out = vec![];
+        run(
+            &Schema::default(),
+            RecordStream::new(input),
+            width,
+            &mut out,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn canonical() {
+        let input = b"hcl:#888785\nxyz:1 hgt:164cm byr:2001 iyr:2015 cid:88\npid:545766238 ecl:hzl\neyr:2022\n\n\n\niyr:2010 byr:1944 byr:1945\n";
+        assert_eq!(
+            format(input, 40),
+            "byr:2001 iyr:2015 eyr:2022 hgt:164cm\nhcl:#888785 ecl:hzl pid:545766238 cid:88\nxyz:1\n\nbyr:1944 byr:1945 iyr:2010\n"
+        );
+        assert_eq!(format(format(input, 40).as_bytes(), 40), format(input, 40));
+        assert_eq!(format(input, 0).lines().count(), 13);
+    }
+
+    #[test]
+    fn unparseable() {
+        assert_eq!(format(b"pid:1 byr\nbyr:1990\n", 80), "pid:1 byr byr:1990\n");
+    }
+}
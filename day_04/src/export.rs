@@ -0,0 +1,191 @@
+use crate::schema::{Mode, Schema, Value, Violation};
+use crate::Record;
+use std::io;
+use std::io::prelude::*;
+
+pub enum Format {
+    JsonLines,
+    Csv,
+}
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "json" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!(
+                "unkown export format '{}', expected json or csv",
+                name
+            )),
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+fn json_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+/// Writes every record with its schema fields normalised, whether it is
+/// valid and its violations. Missing and invalid fields are left empty, the
+/// violations quoting invalid values as found in the input.
+pub fn export<I, W>(
+    schema: &Schema,
+    mode: Mode,
+    records: I,
+    format: Format,
+    mut out: W,
+) -> io::Result<()>
+where
+    I: Iterator<Item = Record>,
+    W: Write,
+{
+    let columns: Vec<String> = schema.fields.iter().map(|f| f.column()).collect();
+    if let Format::Csv = format {
+        let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+        writeln!(out, "line,{},status,violations", header.join(","))?;
+    }
+
+    for record in records {
+        let cells = schema.fields.iter().map(|f| {
+            record
+                .get(&f.key)
+                .and_then(|value| f.field_type.normalise(value).ok())
+        });
+        let violations: Vec<String> = schema
+            .violations(&record, mode)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        let status = if violations.is_empty() {
+            "valid"
+        } else {
+            "invalid"
+        };
+
+        match format {
+            Format::JsonLines => {
+                let fields: Vec<String> = columns
+                    .iter()
+                    .zip(cells)
+                    .map(|(column, cell)| {
+                        let value = match cell {
+                            None => String::from("null"),
+                            Some(Value::Integer(n)) => n.to_string(),
+                            Some(Value::Decimal(n)) => n.to_string(),
+                            Some(Value::Text(s)) => json_string(&s),
+                        };
+                        format!("{}:{}", json_string(column), value)
+                    })
+                    .collect();
+                let violations: Vec<String> = violations.iter().map(|v| json_string(v)).collect();
+                writeln!(
+                    out,
+                    "{{\"line\":{},{},\"status\":\"{}\",\"violations\":[{}]}}",
+                    record.line,
+                    fields.join(","),
+                    status,
+                    violations.join(",")
+                )?;
+            }
+            Format::Csv => {
+                let fields: Vec<String> = cells
+                    .map(|cell| match cell {
+                        None => String::new(),
+                        Some(Value::Integer(n)) => n.to_string(),
+                        Some(Value::Decimal(n)) => n.to_string(),
+                        Some(Value::Text(s)) => csv_field(&s),
+                    })
+                    .collect();
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    record.line,
+                    fields.join(","),
+                    status,
+                    csv_field(&violations.join("; "))
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecordStream;
+
+    fn export_example(format: Format) -> Vec<String> {
+        export_file(&include_bytes!("../example_all_valid.txt")[..], format)
+    }
+
+    fn export_file(input: &[u8], format: Format) -> Vec<String> {
+        let records = RecordStream::new(input).take(2);
+        let mut out = vec![];
+        export(&Schema::default(), Mode::Lenient, records, format, &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn json_lines() {
+        assert_eq!(
+            export_example(Format::JsonLines),
+            vec![
+                "{\"line\":1,\"byr\":1980,\"iyr\":2012,\"eyr\":2030,\"hgt_cm\":187.96,\"hcl\":\"#623a2f\",\"ecl\":\"green\",\"pid\":\"087499704\",\"cid\":null,\"status\":\"valid\",\"violations\":[]}",
+                "{\"line\":4,\"byr\":1989,\"iyr\":2014,\"eyr\":2029,\"hgt_cm\":165,\"hcl\":\"#a97842\",\"ecl\":\"blue\",\"pid\":\"896056539\",\"cid\":\"129\",\"status\":\"valid\",\"violations\":[]}",
+            ]
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let records = RecordStream::new(&b"byr:1990 hgt:180 ecl:brn pid:0123\n"[..]);
+        let mut out = vec![];
+        export(
+            &Schema::default(),
+            Mode::Lenient,
+            records,
+            Format::Csv,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "line,byr,iyr,eyr,hgt_cm,hcl,ecl,pid,cid,status,violations"
+        );
+        assert_eq!(
+            lines[1],
+            "1,1990,,,,,brown,,,invalid,iyr is missing; eyr is missing; hgt '180' is unparseable; hcl is missing; pid '0123' does not match ^\\d{9}$"
+        );
+        assert_eq!(
+            export_example(Format::Csv)[1],
+            "1,1980,2012,2030,187.96,#623a2f,green,087499704,,valid,"
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let input = &include_bytes!("../example_all_invalid.txt")[..];
+        assert_eq!(
+            export_file(input, Format::Csv)[1],
+            "1,1926,2018,,,#18171d,amber,,100,invalid,eyr '1972' is not in 2020..=2030; hgt '170' is unparseable; pid '186cm' does not match ^\\d{9}$"
+        );
+        assert_eq!(
+            export_file(input, Format::JsonLines)[0],
+            "{\"line\":1,\"byr\":1926,\"iyr\":2018,\"eyr\":null,\"hgt_cm\":null,\"hcl\":\"#18171d\",\"ecl\":\"amber\",\"pid\":null,\"cid\":\"100\",\"status\":\"invalid\",\"violations\":[\"eyr '1972' is not in 2020..=2030\",\"hgt '170' is unparseable\",\"pid '186cm' does not match ^\\\\d{9}$\"]}"
+        );
+    }
+}
@@ -1,185 +1,83 @@
-use regex::Regex;
-use std::default::*;
+use export::Format;
+use schema::{Mode, Schema, Violation};
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
 #[macro_use]
 extern crate lazy_static;
 
-#[derive(Default, Debug)]
-struct Passport {
-    byr: bool,
-    iyr: bool,
-    eyr: bool,
-    hgt: bool,
-    hcl: bool,
-    ecl: bool,
-    pid: bool,
-    cid: bool,
-}
-impl Passport {
-    fn is_valid(&self) -> bool {
-        self.byr && self.iyr && self.eyr && self.hgt && self.hcl && self.ecl && self.pid
-    }
-}
-
-#[derive(Default, Debug)]
-struct StrictPassport {
-    byr: Option<BirthYear>,
-    iyr: Option<IssueYear>,
-    eyr: Option<ExpirationYear>,
-    hgt: Option<Height>,
-    hcl: Option<HairColor>,
-    ecl: Option<EyeColor>,
-    pid: Option<PassportID>,
-    cid: Option<CountryID>,
-}
-impl StrictPassport {
-    fn is_valid(&self) -> bool {
-        self.byr.as_ref().map_or(false, BirthYear::is_valid)
-            && self.iyr.as_ref().map_or(false, IssueYear::is_valid)
-            && self.eyr.as_ref().map_or(false, ExpirationYear::is_valid)
-            && self.hgt.as_ref().map_or(false, Height::is_valid)
-            && self.hcl.is_some()
-            && self.ecl.is_some()
-            && self.pid.is_some()
-    }
-}
+mod canonical;
+mod export;
+mod rule;
+mod schema;
 
-#[derive(Debug)]
-struct BirthYear(usize);
-impl BirthYear {
-    fn is_valid(&self) -> bool {
-        1920 <= self.0 && self.0 <= 2002
-    }
+/// Where a token starts in the input, counting lines and characters from 1.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
 }
-
-#[derive(Debug)]
-struct IssueYear(usize);
-impl IssueYear {
-    fn is_valid(&self) -> bool {
-        2010 <= self.0 && self.0 <= 2020
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
     }
 }
 
 #[derive(Debug)]
-struct ExpirationYear(usize);
-impl ExpirationYear {
-    fn is_valid(&self) -> bool {
-        2020 <= self.0 && self.0 <= 2030
-    }
+pub struct Field {
+    pub key: String,
+    pub value: String,
+    pub position: Position,
 }
 
-#[derive(Debug)]
-enum Height {
-    Cm(usize),
-    In(usize),
-}
-impl Height {
-    fn parse(string: &str) -> Result<Height, &'static str> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^(\d+)(cm|in)$").unwrap();
-        }
-        match RE.captures(string) {
-            Some(caps) => {
-                let value = caps.get(1).unwrap().as_str().parse().unwrap();
-                let unit = caps.get(2).unwrap().as_str();
-                match unit {
-                    "cm" => Ok(Height::Cm(value)),
-                    "in" => Ok(Height::In(value)),
-                    _ => Err("unkown unit"),
-                }
-            }
-            None => Err("Wrong format"),
-        }
-    }
-    fn is_valid(&self) -> bool {
-        match *self {
-            Height::Cm(v) => 150 <= v && v <= 193,
-            Height::In(v) => 59 <= v && v <= 76,
-        }
-    }
-}
-#[derive(Debug)]
-struct HairColor(String);
-impl HairColor {
-    fn parse(input: &str) -> Result<HairColor, &'static str> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^#[0-9a-z]{6}$").unwrap();
-        }
-        if RE.is_match(input) {
-            Ok(HairColor(String::from(input)))
-        } else {
-            Err("unkown hair color format")
-        }
-    }
-}
-
-#[derive(Debug)]
-enum EyeColor {
-    Amber,
-    Blue,
-    Brown,
-    Grey,
-    Green,
-    Hazel,
-    Other,
+/// The `key:value` fields of one document, in input order.
+#[derive(Default, Debug)]
+pub struct Record {
+    /// The line the record starts at, counting from 1.
+    pub line: usize,
+    pub fields: Vec<Field>,
+    /// Tokens that are not like `key:value`.
+    pub malformed: Vec<(String, Position)>,
 }
-impl EyeColor {
-    fn parse(input: &str) -> Result<EyeColor, &'static str> {
-        match input {
-            "amb" => Ok(EyeColor::Amber),
-            "blu" => Ok(EyeColor::Blue),
-            "brn" => Ok(EyeColor::Brown),
-            "gry" => Ok(EyeColor::Grey),
-            "grn" => Ok(EyeColor::Green),
-            "hzl" => Ok(EyeColor::Hazel),
-            "oth" => Ok(EyeColor::Other),
-            _ => Err("Unkown eye color"),
-        }
+impl Record {
+    /// The value of `key`, the last one if it is repeated.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|f| f.key == key)
+            .map(|f| f.value.as_str())
     }
 }
 
-#[derive(Debug)]
-struct PassportID(String);
-impl PassportID {
-    fn parse(input: &str) -> Result<PassportID, &'static str> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^\d{9}$").unwrap();
-        }
-        if RE.is_match(input) {
-            Ok(PassportID(String::from(input)))
-        } else {
-            Err("Unkown passport id format")
-        }
-    }
-}
-
-#[derive(Debug)]
-struct CountryID(String);
-
-struct PassportStream<R: BufRead> {
+/// Records separated by one or more blank lines, their fields spread over one
+/// or more lines. Records are never empty.
+struct RecordStream<R: BufRead> {
     lines: std::io::Lines<R>,
+    /// Lines read so far.
+    line: usize,
     done: bool,
 }
-impl<R: BufRead> PassportStream<R> {
-    fn new(reader: R) -> PassportStream<R> {
-        PassportStream {
+impl<R: BufRead> RecordStream<R> {
+    fn new(reader: R) -> RecordStream<R> {
+        RecordStream {
             lines: reader.lines(),
+            line: 0,
             done: false,
         }
     }
 }
-impl<R: BufRead> Iterator for PassportStream<R> {
-    type Item = Passport;
+impl<R: BufRead> Iterator for RecordStream<R> {
+    type Item = Record;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.done {
-            return None;
-        }
-        let mut passport: Passport = Default::default();
+        let mut record: Record = Default::default();
 
-        loop {
+        while !self.done {
             let line = match self.lines.next() {
                 None => {
                     self.done = true;
@@ -188,110 +86,264 @@ impl<R: BufRead> Iterator for PassportStream<R> {
                 Some(Err(_)) => panic!("error"),
                 Some(Ok(l)) => l,
             };
+            self.line += 1;
 
-            match line.as_str() {
-                "" => break,
-                _ => {
-                    for property in line.split_whitespace() {
-                        let split: Vec<&str> = property.split(":").collect();
-                        let key = split[0];
-                        match key {
-                            "byr" => passport.byr = true,
-                            "iyr" => passport.iyr = true,
-                            "eyr" => passport.eyr = true,
-                            "hgt" => passport.hgt = true,
-                            "hcl" => passport.hcl = true,
-                            "ecl" => passport.ecl = true,
-                            "pid" => passport.pid = true,
-                            "cid" => passport.cid = true,
-                            _ => (),
-                        }
+            if line.trim().is_empty() {
+                if record.line == 0 {
+                    continue;
+                }
+                break;
+            }
+            if record.line == 0 {
+                record.line = self.line;
+            }
+            for (column, property) in tokens(&line) {
+                let position = Position {
+                    line: self.line,
+                    column,
+                };
+                match property.split_once(':') {
+                    Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+                        record.fields.push(Field {
+                            key: String::from(key),
+                            value: String::from(value),
+                            position,
+                        })
                     }
+                    _ => record.malformed.push((String::from(property), position)),
                 }
             }
         }
 
-        Some(passport)
+        if record.line == 0 {
+            None
+        } else {
+            Some(record)
+        }
     }
 }
 
-struct StrictPassportStream<R: BufRead> {
-    lines: std::io::Lines<R>,
-    done: bool,
-}
-impl<R: BufRead> StrictPassportStream<R> {
-    fn new(reader: R) -> StrictPassportStream<R> {
-        StrictPassportStream {
-            lines: reader.lines(),
-            done: false,
+/// The whitespace separated tokens of `line` and the columns they start at.
+fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut column = 1;
+    line.split(char::is_whitespace).filter_map(move |token| {
+        let start = column;
+        column += token.chars().count() + 1;
+        if token.is_empty() {
+            None
+        } else {
+            Some((start, token))
         }
-    }
+    })
 }
-impl<R: BufRead> Iterator for StrictPassportStream<R> {
-    type Item = StrictPassport;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.done {
-            return None;
+/// Prints how many records are valid and how many times each kind of
+/// violation occurs, most frequent first.
+fn print_summary<I: Iterator<Item = Record>>(schema: &Schema, mode: Mode, records: I) {
+    let mut total = 0;
+    let mut valid = 0;
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for record in records {
+        let violations = schema.violations(&record, mode);
+        total += 1;
+        if violations.is_empty() {
+            valid += 1;
+        }
+        for violation in violations {
+            *counts.entry(violation.category()).or_insert(0) += 1;
         }
-        let mut passport: StrictPassport = Default::default();
+    }
 
-        loop {
-            let line = match self.lines.next() {
-                None => {
-                    self.done = true;
-                    break;
-                }
-                Some(Err(_)) => panic!("error"),
-                Some(Ok(l)) => l,
-            };
+    println!(
+        "{} records, {} valid, {} invalid",
+        total,
+        valid,
+        total - valid
+    );
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    for (category, count) in counts {
+        println!("{:>6} {}", count, category);
+    }
+}
 
-            match line.as_str() {
-                "" => break,
-                _ => {
-                    for property in line.split_whitespace() {
-                        let split: Vec<&str> = property.split(":").collect();
-                        let key = split[0];
-                        let value = split[1];
-                        match key {
-                            "byr" => passport.byr = Some(BirthYear(value.parse().unwrap())),
-                            "iyr" => passport.iyr = Some(IssueYear(value.parse().unwrap())),
-                            "eyr" => passport.eyr = Some(ExpirationYear(value.parse().unwrap())),
-                            "hgt" => passport.hgt = Height::parse(value).ok(),
-                            "hcl" => passport.hcl = HairColor::parse(value).ok(),
-                            "ecl" => passport.ecl = EyeColor::parse(value).ok(),
-                            "pid" => passport.pid = PassportID::parse(value).ok(),
-                            "cid" => passport.cid = Some(CountryID(String::from(value))),
-                            _ => (),
-                        }
-                    }
-                }
-            }
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => {
+            args.remove(i);
+            true
         }
+        None => false,
+    }
+}
 
-        Some(passport)
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    args.remove(i);
+    if i < args.len() {
+        Some(args.remove(i))
+    } else {
+        None
     }
 }
 
-fn main() {
-    let stdin = io::stdin();
+fn main() -> io::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mode = if take_flag(&mut args, "--strict") {
+        Mode::Strict
+    } else {
+        Mode::Lenient
+    };
+    let width = match take_option(&mut args, "--width").map(|w| w.parse()) {
+        None => 80,
+        Some(Ok(width)) => width,
+        Some(Err(e)) => {
+            eprintln!("invalid width: {}", e);
+            return Ok(());
+        }
+    };
+    let schema = match take_option(&mut args, "--schema") {
+        Some(path) => Schema::parse(BufReader::new(File::open(path)?)),
+        None => Ok(Schema::default()),
+    };
+    let schema = match schema {
+        Ok(schema) => schema,
+        Err(message) => {
+            eprintln!("{}", message);
+            return Ok(());
+        }
+    };
 
-    let part: String = env::args().nth(1).unwrap();
-    match part.as_str() {
-        "part_1" => {
-            let result = PassportStream::new(stdin.lock())
-                .filter(Passport::is_valid)
+    let stdin = io::stdin();
+    match args.first().map(String::as_str) {
+        Some("part_1") => {
+            let result = RecordStream::new(stdin.lock())
+                .filter(|r| schema.has_required_fields(r))
                 .count();
             println!("{}", result);
         }
-        "part_2" => {
-            let result = StrictPassportStream::new(stdin.lock())
-                .filter(StrictPassport::is_valid)
+        Some("part_2") => {
+            let result = RecordStream::new(stdin.lock())
+                .filter(|r| schema.is_valid(r, mode))
                 .count();
             println!("{}", result);
         }
+        Some("report") => {
+            for record in RecordStream::new(stdin.lock()) {
+                let violations = schema.violations(&record, mode);
+                if !violations.is_empty() {
+                    let violations: Vec<String> =
+                        violations.iter().map(Violation::to_string).collect();
+                    println!("line {}: {}", record.line, violations.join(", "));
+                }
+            }
+        }
+        Some("summary") => print_summary(&schema, mode, RecordStream::new(stdin.lock())),
+        Some("fmt") => {
+            let out = BufWriter::new(io::stdout().lock());
+            canonical::run(&schema, RecordStream::new(stdin.lock()), width, out)?;
+        }
+        Some("export") => match Format::parse(args.get(1).map_or("json", String::as_str)) {
+            Ok(format) => {
+                let out = BufWriter::new(io::stdout().lock());
+                export::export(&schema, mode, RecordStream::new(stdin.lock()), format, out)?;
+            }
+            Err(message) => eprintln!("{}", message),
+        },
         _ => {
             println!("unkown part")
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_all_valid() {
+        let schema = Schema::default();
+        let records: Vec<Record> =
+            RecordStream::new(&include_bytes!("../example_all_valid.txt")[..]).collect();
+        assert_eq!(records.len(), 4);
+        assert!(records.iter().all(|r| schema.is_valid(r, Mode::Strict)));
+        let lines: Vec<usize> = records.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![1, 4, 7, 12]);
+    }
+
+    #[test]
+    fn example_all_invalid() {
+        let schema = Schema::default();
+        let violations: Vec<Vec<String>> =
+            RecordStream::new(&include_bytes!("../example_all_invalid.txt")[..])
+                .map(|r| {
+                    schema
+                        .violations(&r, Mode::Lenient)
+                        .iter()
+                        .map(Violation::category)
+                        .collect()
+                })
+                .collect();
+        assert_eq!(
+            violations,
+            vec![
+                vec![
+                    "eyr: not in 2020..=2030",
+                    "hgt: unparseable",
+                    "pid: does not match ^\\d{9}$",
+                ],
+                vec!["eyr: not in 2020..=2030"],
+                vec!["hcl: does not match ^#[0-9a-f]{6}$"],
+                vec![
+                    "byr: not in 1920..=2002",
+                    "iyr: not in 2010..=2020",
+                    "eyr: not in 2020..=2030",
+                    "hgt: not in 150..=193cm",
+                    "hcl: does not match ^#[0-9a-f]{6}$",
+                    "ecl: unkown value",
+                    "pid: does not match ^\\d{9}$",
+                ],
+                vec!["hcl: does not match ^#[0-9a-f]{6}$"],
+                vec!["byr: unparseable"],
+                vec!["iyr: unparseable", "eyr: unparseable"],
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_positions() {
+        let record = RecordStream::new(&b"\nbyr:1990  iyr\n\thgt:170cm :x byr:2030\n"[..])
+            .next()
+            .unwrap();
+        assert_eq!(record.line, 2);
+        let fields: Vec<(&str, Position)> = record
+            .fields
+            .iter()
+            .map(|f| (f.key.as_str(), f.position))
+            .collect();
+        let at = |line, column| Position { line, column };
+        assert_eq!(
+            fields,
+            vec![("byr", at(2, 1)), ("hgt", at(3, 2)), ("byr", at(3, 15))]
+        );
+        assert_eq!(
+            record.malformed,
+            vec![
+                (String::from("iyr"), at(2, 11)),
+                (String::from(":x"), at(3, 12))
+            ]
+        );
+        assert_eq!(record.get("byr"), Some("2030"));
+    }
+
+    #[test]
+    fn blank_lines() {
+        let records: Vec<Record> =
+            RecordStream::new(&b"\n \nbyr:1990\n\n\n\niyr:2010\n\n\n"[..]).collect();
+        let lines: Vec<usize> = records.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![3, 7]);
+        assert_eq!(records[1].get("iyr"), Some("2010"));
+        assert_eq!(RecordStream::new(&b"\n\n"[..]).count(), 0);
+    }
 }
@@ -0,0 +1,173 @@
+use crate::Record;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+impl Comparison {
+    fn parse(input: &str) -> Option<Comparison> {
+        match input {
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            _ => None,
+        }
+    }
+
+    fn holds(self, left: i128, right: i128) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Condition {
+    /// `left <comparison> right + offset`, the fields read as numbers.
+    Compare {
+        left: String,
+        comparison: Comparison,
+        right: String,
+        offset: i64,
+    },
+    /// `key` must be present when `field` is one of `values`.
+    RequiredIf {
+        key: String,
+        field: String,
+        values: Vec<String>,
+    },
+}
+
+/// A rule relating several fields of a record.
+#[derive(Debug)]
+pub struct Rule {
+    /// The rule as written in the schema.
+    pub text: String,
+    pub condition: Condition,
+}
+impl Rule {
+    /// Parses `iyr >= byr + 16`, `eyr > iyr` or `cid required if iss in FRA NPL`.
+    pub fn parse(words: &[&str]) -> Result<Rule, String> {
+        let text = words.join(" ");
+        let error = || {
+            format!(
+                "'{}' is not a rule like 'iyr >= byr + 16' or 'cid required if iss in FRA'",
+                text
+            )
+        };
+        let condition = match words {
+            [key, "required", "if", field, "in", values @ ..] if !values.is_empty() => {
+                Condition::RequiredIf {
+                    key: String::from(*key),
+                    field: String::from(*field),
+                    values: values.iter().map(|v| String::from(*v)).collect(),
+                }
+            }
+            [left, comparison, right, offset @ ..] => {
+                let comparison = Comparison::parse(comparison).ok_or_else(error)?;
+                let offset = match offset {
+                    [] => 0,
+                    ["+", n] => n.parse().map_err(|_| error())?,
+                    ["-", n] => -n.parse::<i64>().map_err(|_| error())?,
+                    _ => return Err(error()),
+                };
+                Condition::Compare {
+                    left: String::from(*left),
+                    comparison,
+                    right: String::from(*right),
+                    offset,
+                }
+            }
+            _ => return Err(error()),
+        };
+        Ok(Rule { text, condition })
+    }
+
+    /// The fields the rule refers to.
+    pub fn keys(&self) -> Vec<&str> {
+        match &self.condition {
+            Condition::Compare { left, right, .. } => vec![left, right],
+            Condition::RequiredIf { key, field, .. } => vec![key, field],
+        }
+    }
+
+    /// Whether `record` follows the rule. Comparisons of missing or non
+    /// numeric fields hold, those fields being reported on their own.
+    pub fn holds(&self, record: &Record) -> bool {
+        match &self.condition {
+            Condition::Compare {
+                left,
+                comparison,
+                right,
+                offset,
+            } => {
+                let number = |key: &str| record.get(key).and_then(|v| v.parse::<i64>().ok());
+                match (number(left), number(right)) {
+                    (Some(left), Some(right)) => {
+                        // Widened so that adding the offset cannot overflow.
+                        comparison.holds(left as i128, right as i128 + *offset as i128)
+                    }
+                    _ => true,
+                }
+            }
+            Condition::RequiredIf { key, field, values } => {
+                record.get(key).is_some()
+                    || !record
+                        .get(field)
+                        .is_some_and(|value| values.iter().any(|v| v == value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(text: &str) -> Rule {
+        Rule::parse(&text.split_whitespace().collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn holds() {
+        let record = crate::RecordStream::new(&b"byr:1990 iyr:2006 eyr:2016 iss:FRA\n"[..])
+            .next()
+            .unwrap();
+        assert!(rule("iyr >= byr + 16").holds(&record));
+        assert!(!rule("iyr >= byr + 17").holds(&record));
+        assert!(rule("eyr <= iyr + 10").holds(&record));
+        assert!(!rule("eyr < iyr + 10").holds(&record));
+        assert!(rule("byr != eyr - 1").holds(&record));
+        assert!(rule("hgt > byr").holds(&record));
+        assert!(!rule("cid required if iss in NPL FRA").holds(&record));
+        assert!(rule("cid required if iss in NPL").holds(&record));
+
+        let record = crate::RecordStream::new(&b"byr:1990 iyr:9223372036854775807\n"[..])
+            .next()
+            .unwrap();
+        assert!(rule("iyr >= byr + 16").holds(&record));
+        assert!(!rule("byr >= iyr + 1").holds(&record));
+        assert!(rule("byr > iyr - 9223372036854775807").holds(&record));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Rule::parse(&["iyr", ">="]).is_err());
+        assert!(Rule::parse(&["iyr", "=>", "byr"]).is_err());
+        assert!(Rule::parse(&["iyr", ">=", "byr", "*", "2"]).is_err());
+        assert!(Rule::parse(&["cid", "required", "if", "iss", "in"]).is_err());
+    }
+}
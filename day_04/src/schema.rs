@@ -0,0 +1,542 @@
+use crate::rule::Rule;
+use crate::{Position, Record};
+use regex::Regex;
+use std::fmt;
+use std::io::prelude::*;
+
+#[derive(Debug)]
+pub struct Unit {
+    pub name: String,
+    pub min: u64,
+    pub max: u64,
+    /// How many of the first unit of the measurement this unit is.
+    pub factor: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct EnumValue {
+    pub code: String,
+    /// The value spelled out.
+    pub label: String,
+}
+
+/// A valid value, converted to the first unit for measurements and spelled
+/// out for enums.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Integer(u64),
+    Decimal(f64),
+    Text(String),
+}
+
+#[derive(Debug)]
+pub enum FieldType {
+    Year {
+        min: u64,
+        max: u64,
+    },
+    /// A number followed by one of the units, e.g. `183cm`.
+    Measurement(Vec<Unit>),
+    Pattern(Regex),
+    Enum(Vec<EnumValue>),
+    Text,
+}
+impl FieldType {
+    fn parse(name: &str, arguments: &[&str]) -> Result<FieldType, String> {
+        match (name, arguments) {
+            ("year", [range]) => {
+                let (min, max) = parse_range(range)?;
+                Ok(FieldType::Year { min, max })
+            }
+            ("measurement", units) if !units.is_empty() && units.len() % 2 == 0 => {
+                let units = units
+                    .chunks(2)
+                    .enumerate()
+                    .map(|(i, unit)| {
+                        let (min, max) = parse_range(unit[1])?;
+                        let (name, factor) = match unit[0].split_once('=') {
+                            Some((name, factor)) => (
+                                name,
+                                Some(
+                                    factor
+                                        .parse::<f64>()
+                                        .ok()
+                                        .filter(|f| f.is_finite() && *f > 0.0)
+                                        .ok_or_else(|| {
+                                            format!("'{}' is not a unit like 'in=2.54'", unit[0])
+                                        })?,
+                                ),
+                            ),
+                            None if i == 0 => (unit[0], Some(1.0)),
+                            None => (unit[0], None),
+                        };
+                        Ok(Unit {
+                            name: String::from(name),
+                            min,
+                            max,
+                            factor,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok(FieldType::Measurement(units))
+            }
+            ("regex", [pattern]) => Regex::new(pattern)
+                .map(FieldType::Pattern)
+                .map_err(|e| format!("invalid regex: {}", e)),
+            ("enum", values) if !values.is_empty() => Ok(FieldType::Enum(
+                values
+                    .iter()
+                    .map(|v| {
+                        let (code, label) = v.split_once('=').unwrap_or((v, v));
+                        EnumValue {
+                            code: String::from(code),
+                            label: String::from(label),
+                        }
+                    })
+                    .collect(),
+            )),
+            ("text", []) => Ok(FieldType::Text),
+            _ => Err(format!("unkown type '{} {}'", name, arguments.join(" "))),
+        }
+    }
+
+    /// The normalised value, or why `value` does not follow the rule.
+    pub fn normalise(&self, value: &str) -> Result<Value, Problem> {
+        lazy_static! {
+            static ref MEASUREMENT: Regex = Regex::new(r"^(\d+)([a-z]+)$").unwrap();
+        }
+        match self {
+            FieldType::Year { min, max } => {
+                if value.len() != 4 || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(Problem::Unparseable);
+                }
+                let year = value.parse().unwrap();
+                check_range(year, *min, *max, "")?;
+                Ok(Value::Integer(year))
+            }
+            FieldType::Measurement(units) => {
+                let caps = MEASUREMENT.captures(value).ok_or(Problem::Unparseable)?;
+                let amount: u64 = caps[1].parse().map_err(|_| Problem::Unparseable)?;
+                let unit = units
+                    .iter()
+                    .find(|unit| unit.name == caps[2])
+                    .ok_or_else(|| Problem::UnknownUnit(String::from(&caps[2])))?;
+                check_range(amount, unit.min, unit.max, &unit.name)?;
+                Ok(match unit.factor {
+                    Some(1.0) => Value::Integer(amount),
+                    Some(factor) => {
+                        Value::Decimal((amount as f64 * factor * 100.0).round() / 100.0)
+                    }
+                    None => Value::Text(String::from(value)),
+                })
+            }
+            FieldType::Pattern(regex) if regex.is_match(value) => {
+                Ok(Value::Text(String::from(value)))
+            }
+            FieldType::Pattern(regex) => Err(Problem::NoMatch(String::from(regex.as_str()))),
+            FieldType::Enum(values) => values
+                .iter()
+                .find(|v| v.code == value)
+                .map(|v| Value::Text(v.label.clone()))
+                .ok_or(Problem::NotAllowed),
+            FieldType::Text => Ok(Value::Text(String::from(value))),
+        }
+    }
+}
+
+fn check_range(amount: u64, min: u64, max: u64, unit: &str) -> Result<(), Problem> {
+    if (min..=max).contains(&amount) {
+        Ok(())
+    } else {
+        Err(Problem::OutOfRange(format!("{}..={}{}", min, max, unit)))
+    }
+}
+
+/// Why a value does not follow the rule of its field.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// Not a four-digit year, or not a number followed by a unit.
+    Unparseable,
+    /// Outside of the given range.
+    OutOfRange(String),
+    UnknownUnit(String),
+    /// Does not match the given pattern.
+    NoMatch(String),
+    /// Not one of the enum values.
+    NotAllowed,
+}
+
+/// Whether records may hold more than the fields of the schema.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// Unknown fields and malformed tokens are ignored, and the last of
+    /// repeated fields wins.
+    Lenient,
+    /// Unknown, repeated fields and malformed tokens are violations.
+    Strict,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Violation {
+    Missing(String),
+    Duplicate(String, Position),
+    Unknown(String, Position),
+    Malformed(String, Position),
+    /// A rule relating several fields does not hold.
+    Rule(String),
+    Invalid {
+        key: String,
+        value: String,
+        problem: Problem,
+    },
+}
+impl Violation {
+    /// What went wrong without the offending value, to count similar
+    /// violations together.
+    pub fn category(&self) -> String {
+        match self {
+            Violation::Missing(key) => format!("{}: missing", key),
+            Violation::Duplicate(key, _) => format!("{}: duplicate", key),
+            Violation::Unknown(key, _) => format!("{}: unkown field", key),
+            Violation::Malformed(..) => String::from("malformed token"),
+            Violation::Rule(rule) => format!("rule: {}", rule),
+            Violation::Invalid { key, problem, .. } => match problem {
+                Problem::Unparseable => format!("{}: unparseable", key),
+                Problem::OutOfRange(range) => format!("{}: not in {}", key, range),
+                Problem::UnknownUnit(_) => format!("{}: unkown unit", key),
+                Problem::NoMatch(pattern) => format!("{}: does not match {}", key, pattern),
+                Problem::NotAllowed => format!("{}: unkown value", key),
+            },
+        }
+    }
+}
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Missing(key) => write!(f, "{} is missing", key),
+            Violation::Duplicate(key, position) => write!(f, "{} is repeated at {}", key, position),
+            Violation::Unknown(key, position) => write!(f, "{} at {} is unkown", key, position),
+            Violation::Rule(rule) => write!(f, "rule '{}' does not hold", rule),
+            Violation::Malformed(token, position) => {
+                write!(f, "'{}' at {} is not like key:value", token, position)
+            }
+            Violation::Invalid {
+                key,
+                value,
+                problem,
+            } => {
+                write!(f, "{} '{}' ", key, value)?;
+                match problem {
+                    Problem::Unparseable => write!(f, "is unparseable"),
+                    Problem::OutOfRange(range) => write!(f, "is not in {}", range),
+                    Problem::UnknownUnit(unit) => write!(f, "has unkown unit '{}'", unit),
+                    Problem::NoMatch(pattern) => write!(f, "does not match {}", pattern),
+                    Problem::NotAllowed => write!(f, "is not an allowed value"),
+                }
+            }
+        }
+    }
+}
+
+/// Parses an inclusive range like `1920..=2002`.
+fn parse_range(input: &str) -> Result<(u64, u64), String> {
+    let error = || format!("'{}' is not a range like '1920..=2002'", input);
+    let i = input.find("..=").ok_or_else(error)?;
+    let min = input[..i].parse().map_err(|_| error())?;
+    let max = input[i + 3..].parse().map_err(|_| error())?;
+    Ok((min, max))
+}
+
+#[derive(Debug)]
+pub struct FieldSpec {
+    pub key: String,
+    pub required: bool,
+    pub field_type: FieldType,
+}
+impl FieldSpec {
+    /// The name of the field in exports, with the unit of measurements.
+    pub fn column(&self) -> String {
+        match &self.field_type {
+            FieldType::Measurement(units) => format!("{}_{}", self.key, units[0].name),
+            _ => self.key.clone(),
+        }
+    }
+}
+
+/// The fields of a kind of document and the rules their values follow.
+#[derive(Debug)]
+pub struct Schema {
+    pub fields: Vec<FieldSpec>,
+    pub rules: Vec<Rule>,
+}
+impl Schema {
+    /// Parses one field per line as `<key>[?] <type> <arguments>`, e.g.
+    /// `byr year 1920..=2002`, or one rule relating fields as `rule <rule>`,
+    /// e.g. `rule iyr >= byr + 16`. Keys ending with `?` are optional. Blank
+    /// lines and lines starting with `#` are ignored.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Schema, String> {
+        let mut fields: Vec<FieldSpec> = vec![];
+        let mut rules: Vec<(usize, Rule)> = vec![];
+        for (i, line) in reader.lines().map_while(Result::ok).enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (key, type_name, arguments) = match words.as_slice() {
+                [] => continue,
+                [first, ..] if first.starts_with('#') => continue,
+                ["rule", rule @ ..] => {
+                    let rule =
+                        Rule::parse(rule).map_err(|e| format!("schema line {}: {}", i + 1, e))?;
+                    rules.push((i + 1, rule));
+                    continue;
+                }
+                [key, type_name, arguments @ ..] => (*key, *type_name, arguments),
+                _ => {
+                    return Err(format!(
+                        "schema line {}: '{}' is not like 'byr year 1920..=2002'",
+                        i + 1,
+                        line
+                    ))
+                }
+            };
+            let (key, required) = match key.strip_suffix('?') {
+                Some(key) => (key, false),
+                None => (key, true),
+            };
+            if fields.iter().any(|f| f.key == key) {
+                return Err(format!(
+                    "schema line {}: '{}' is already in the schema",
+                    i + 1,
+                    key
+                ));
+            }
+            let field_type = FieldType::parse(type_name, arguments)
+                .map_err(|e| format!("schema line {}: {}", i + 1, e))?;
+            fields.push(FieldSpec {
+                key: String::from(key),
+                required,
+                field_type,
+            });
+        }
+        for (line, rule) in &rules {
+            if let Some(key) = rule
+                .keys()
+                .into_iter()
+                .find(|key| !fields.iter().any(|f| f.key == *key))
+            {
+                return Err(format!(
+                    "schema line {}: '{}' is not in the schema",
+                    line, key
+                ));
+            }
+        }
+        let rules = rules.into_iter().map(|(_, rule)| rule).collect();
+        Ok(Schema { fields, rules })
+    }
+
+    pub fn has_required_fields(&self, record: &Record) -> bool {
+        self.fields
+            .iter()
+            .filter(|f| f.required)
+            .all(|f| record.get(&f.key).is_some())
+    }
+
+    /// The missing required fields and the fields of the schema present in
+    /// `record` with invalid values, in schema order, then the rules that do
+    /// not hold. In strict mode they are preceded by the malformed tokens,
+    /// unknown and repeated fields in input order.
+    pub fn violations(&self, record: &Record, mode: Mode) -> Vec<Violation> {
+        let mut violations = vec![];
+        if mode == Mode::Strict {
+            violations.extend(self.structure_violations(record));
+        }
+        let fields = self.fields.iter().filter_map(|f| match record.get(&f.key) {
+            Some(value) => f
+                .field_type
+                .normalise(value)
+                .err()
+                .map(|problem| Violation::Invalid {
+                    key: f.key.clone(),
+                    value: String::from(value),
+                    problem,
+                }),
+            None if f.required => Some(Violation::Missing(f.key.clone())),
+            None => None,
+        });
+        violations.extend(fields);
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| !rule.holds(record))
+            .map(|rule| Violation::Rule(rule.text.clone()));
+        violations.extend(rules);
+        violations
+    }
+
+    fn structure_violations(&self, record: &Record) -> Vec<Violation> {
+        let mut violations: Vec<(Position, Violation)> = record
+            .malformed
+            .iter()
+            .map(|(token, position)| (*position, Violation::Malformed(token.clone(), *position)))
+            .collect();
+        for (i, field) in record.fields.iter().enumerate() {
+            let violation = if !self.fields.iter().any(|f| f.key == field.key) {
+                Violation::Unknown(field.key.clone(), field.position)
+            } else if record.fields[..i].iter().any(|f| f.key == field.key) {
+                Violation::Duplicate(field.key.clone(), field.position)
+            } else {
+                continue;
+            };
+            violations.push((field.position, violation));
+        }
+        violations.sort_by_key(|(position, _)| (position.line, position.column));
+        violations.into_iter().map(|(_, v)| v).collect()
+    }
+
+    pub fn is_valid(&self, record: &Record, mode: Mode) -> bool {
+        self.violations(record, mode).is_empty()
+    }
+}
+
+impl Default for Schema {
+    fn default() -> Schema {
+        Schema::parse(&include_bytes!("../passport.schema")[..]).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_types() {
+        let schema = Schema::default();
+        let valid = |key: &str, value: &str| {
+            let field = schema.fields.iter().find(|f| f.key == key).unwrap();
+            field.field_type.normalise(value).is_ok()
+        };
+        assert!(valid("byr", "2002"));
+        assert!(!valid("byr", "2003"));
+        assert!(!valid("byr", "20x2"));
+        assert!(!valid("byr", "+2002"));
+        assert!(!valid("byr", "02002"));
+        assert!(valid("hcl", "#123abc"));
+        assert!(!valid("hcl", "#123abz"));
+        assert!(valid("hgt", "60in"));
+        assert!(valid("hgt", "190cm"));
+        assert!(!valid("hgt", "190in"));
+        assert!(!valid("hgt", "190"));
+        assert!(valid("ecl", "brn"));
+        assert!(!valid("ecl", "wat"));
+        assert!(valid("pid", "000000001"));
+        assert!(!valid("pid", "0123456789"));
+
+        let normalise = |key: &str, value: &str| {
+            let field = schema.fields.iter().find(|f| f.key == key).unwrap();
+            field.field_type.normalise(value).unwrap()
+        };
+        assert_eq!(normalise("byr", "1990"), Value::Integer(1990));
+        assert_eq!(normalise("hgt", "183cm"), Value::Integer(183));
+        assert_eq!(normalise("hgt", "74in"), Value::Decimal(187.96));
+        assert_eq!(normalise("ecl", "hzl"), Value::Text(String::from("hazel")));
+        assert_eq!(
+            normalise("pid", "000000001"),
+            Value::Text(String::from("000000001"))
+        );
+    }
+
+    fn parse_record(input: &[u8]) -> Record {
+        crate::RecordStream::new(input).next().unwrap()
+    }
+
+    #[test]
+    fn violations() {
+        let schema = Schema::default();
+        let record = parse_record(b"byr:19x0 iyr:2009 hgt:60ft\necl:wat pid:01234\n");
+        let violations: Vec<String> = schema
+            .violations(&record, Mode::Lenient)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "byr '19x0' is unparseable",
+                "iyr '2009' is not in 2010..=2020",
+                "eyr is missing",
+                "hgt '60ft' has unkown unit 'ft'",
+                "hcl is missing",
+                "ecl 'wat' is not an allowed value",
+                "pid '01234' does not match ^\\d{9}$",
+            ]
+        );
+    }
+
+    #[test]
+    fn strict() {
+        let schema = Schema::default();
+        let record = parse_record(
+            b"byr:1990 iyr:2015 eyr:2025 hgt:180cm\nhcl:#123abc ecl:brn pid:000000001 byr:2030 x:1 y\n",
+        );
+        assert!(!schema.is_valid(&record, Mode::Lenient));
+        let violations: Vec<String> = schema
+            .violations(&record, Mode::Strict)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "byr is repeated at 2:35",
+                "x at 2:44 is unkown",
+                "'y' at 2:48 is not like key:value",
+                "byr '2030' is not in 1920..=2002",
+            ]
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let schema = Schema::parse(&include_bytes!("../consistent.schema")[..]).unwrap();
+        let rules = |input: &[u8]| -> Vec<String> {
+            schema
+                .violations(&parse_record(input), Mode::Lenient)
+                .iter()
+                .map(Violation::to_string)
+                .collect()
+        };
+        assert!(
+            rules(b"byr:1994 iyr:2010 eyr:2020 hgt:180cm hcl:#123abc ecl:brn pid:000000001\n")
+                .is_empty()
+        );
+        assert_eq!(
+            rules(b"byr:1995 iyr:2010 eyr:2021 hgt:180cm hcl:#123abc ecl:brn pid:000000001\n"),
+            vec![
+                "rule 'iyr >= byr + 16' does not hold",
+                "rule 'eyr <= iyr + 10' does not hold"
+            ]
+        );
+        assert_eq!(
+            rules(
+                b"byr:1990 iyr:2020 eyr:2020 hgt:180cm hcl:#123abc ecl:brn pid:000000001 iss:NPL\n"
+            ),
+            vec![
+                "rule 'eyr > iyr' does not hold",
+                "rule 'cid required if iss in NPL FRA' does not hold"
+            ]
+        );
+        assert!(Schema::parse(&b"byr year 1920..=2002\nrule iyr >= byr + 16\n"[..]).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Schema::parse(&b"byr\n"[..]).is_err());
+        assert!(Schema::parse(&b"byr year 1920\n"[..]).is_err());
+        assert!(Schema::parse(&b"hgt measurement cm\n"[..]).is_err());
+        assert!(Schema::parse(&b"hgt measurement cm 1..=2 in=x 1..=2\n"[..]).is_err());
+        for factor in &["NaN", "inf", "-inf", "-2.54", "0"] {
+            let schema = format!("hgt measurement cm 1..=2 in={} 1..=2\n", factor);
+            assert!(Schema::parse(schema.as_bytes()).is_err());
+        }
+        assert!(Schema::parse(&b"byr text\nbyr text\n"[..]).is_err());
+        assert!(Schema::parse(&b"byr date\n"[..]).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{BoardingPass, Geometry};
+use std::io;
+use std::io::prelude::*;
+
+const OCCUPIED: char = '#';
+const EMPTY: char = '.';
+/// A seat in one of the missing front or back rows.
+const NOTHING: char = '-';
+
+/// Which seats of the plane are taken by a boarding pass.
+pub struct Cabin<'a> {
+    geometry: &'a Geometry,
+    /// Indexed by seat id.
+    occupied: Vec<bool>,
+}
+impl<'a> Cabin<'a> {
+    pub fn new(geometry: &'a Geometry, passes: &[BoardingPass]) -> Cabin<'a> {
+        let mut occupied = vec![false; (geometry.rows * geometry.columns) as usize];
+        for pass in passes {
+            occupied[geometry.seat_id(pass) as usize] = true;
+        }
+        Cabin { geometry, occupied }
+    }
+
+    fn exists(&self, seat_id: u32) -> bool {
+        self.geometry.seat(seat_id).is_ok()
+    }
+
+    fn is_occupied(&self, seat_id: u32) -> bool {
+        self.occupied.get(seat_id as usize) == Some(&true)
+    }
+
+    /// One line per row, with its row number.
+    pub fn render<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let width = (self.geometry.rows - 1).to_string().len();
+        for row in 0..self.geometry.rows {
+            let seats: String = (0..self.geometry.columns)
+                .map(|column| {
+                    let seat_id = row * self.geometry.columns + column;
+                    if !self.exists(seat_id) {
+                        NOTHING
+                    } else if self.is_occupied(seat_id) {
+                        OCCUPIED
+                    } else {
+                        EMPTY
+                    }
+                })
+                .collect();
+            writeln!(out, "{:>width$} {}", row, seats, width = width)?;
+        }
+        Ok(())
+    }
+
+    /// The ids of the existing seats nobody has a pass for.
+    pub fn empty_seats(&self) -> Vec<u32> {
+        (0..self.occupied.len() as u32)
+            .filter(|&seat_id| self.exists(seat_id) && !self.is_occupied(seat_id))
+            .collect()
+    }
+
+    /// The empty seats whose ids are between two occupied ones.
+    pub fn candidates(&self) -> Vec<u32> {
+        self.empty_seats()
+            .into_iter()
+            .filter(|&seat_id| {
+                seat_id > 0 && self.is_occupied(seat_id - 1) && self.is_occupied(seat_id + 1)
+            })
+            .collect()
+    }
+
+    /// The only candidate seat.
+    pub fn my_seat(&self) -> Result<u32, String> {
+        match self.candidates().as_slice() {
+            [] => Err(String::from("no empty seat has both neighbours occupied")),
+            [seat_id] => Ok(*seat_id),
+            candidates => Err(format!(
+                "{} empty seats have both neighbours occupied: {}",
+                candidates.len(),
+                format_ids(candidates)
+            )),
+        }
+    }
+}
+
+/// Lists `ids` with runs of consecutive ids as ranges, e.g. `0..=12, 743`.
+pub fn format_ids(ids: &[u32]) -> String {
+    let mut runs: Vec<(u32, u32)> = vec![];
+    for &id in ids {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == id => *last = id,
+            _ => runs.push((id, id)),
+        }
+    }
+    let runs: Vec<String> = runs
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}..={}", first, last)
+            }
+        })
+        .collect();
+    runs.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cabin_with(geometry: &Geometry, seat_ids: &[u32]) -> Vec<BoardingPass> {
+        seat_ids
+            .iter()
+            .map(|&id| geometry.seat(id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn seats() {
+        let geometry = Geometry::new(4, 3, 1, 0).unwrap();
+        let passes = cabin_with(&geometry, &[3, 4, 6, 8, 9, 11]);
+        let cabin = Cabin::new(&geometry, &passes);
+
+        let mut map = vec![];
+        cabin.render(&mut map).unwrap();
+        assert_eq!(
+            String::from_utf8(map).unwrap(),
+            "0 ---\n1 ##.\n2 #.#\n3 #.#\n"
+        );
+        assert_eq!(cabin.empty_seats(), vec![5, 7, 10]);
+        assert_eq!(cabin.candidates(), vec![5, 7, 10]);
+        assert!(cabin.my_seat().is_err());
+
+        let passes = cabin_with(&geometry, &[3, 4, 6, 7, 8]);
+        let cabin = Cabin::new(&geometry, &passes);
+        assert_eq!(format_ids(&cabin.empty_seats()), "5, 9..=11");
+        assert_eq!(cabin.my_seat(), Ok(5));
+
+        let passes = cabin_with(&geometry, &[3, 4, 5]);
+        assert!(Cabin::new(&geometry, &passes).my_seat().is_err());
+    }
+}
@@ -1,36 +1,305 @@
+use cabin::{format_ids, Cabin};
+use std::env;
 use std::io;
 use std::io::prelude::*;
 
+mod cabin;
+
+/// A seat found by binary space partitioning: `F`/`B` halves the rows
+/// keeping the front/back, then `L`/`R` halves the columns.
+#[derive(Debug, PartialEq)]
+struct BoardingPass {
+    row: u32,
+    column: u32,
+}
+
+/// The number of letters needed to tell `count` rows or columns apart.
+fn bits(count: u32) -> u32 {
+    32 - (count - 1).leading_zeros()
+}
+
+/// The `count` lower bits of `value`, most significant first, as `zero`/`one`.
+fn letters(value: u32, count: u32, zero: char, one: char) -> impl Iterator<Item = char> {
+    (0..count)
+        .rev()
+        .map(move |bit| if value >> bit & 1 == 1 { one } else { zero })
+}
+
+/// The seat layout of a plane. Rows and columns need not be powers of two,
+/// passes then having enough letters for the next power of two, and some
+/// rows at the very front and back may not exist.
+#[derive(Debug)]
+struct Geometry {
+    rows: u32,
+    columns: u32,
+    missing_front: u32,
+    missing_back: u32,
+}
+impl Geometry {
+    fn new(
+        rows: u32,
+        columns: u32,
+        missing_front: u32,
+        missing_back: u32,
+    ) -> Result<Geometry, String> {
+        if rows == 0 || columns == 0 {
+            return Err(String::from(
+                "a plane needs at least one row and one column",
+            ));
+        }
+        if rows.checked_mul(columns).is_none() {
+            return Err(format!(
+                "{} rows of {} columns make too many seats, at most {} are supported",
+                rows,
+                columns,
+                u32::MAX
+            ));
+        }
+        let missing = missing_front as u64 + missing_back as u64;
+        if missing >= rows as u64 {
+            return Err(format!(
+                "{} missing rows leave no seats out of {} rows",
+                missing, rows
+            ));
+        }
+        Ok(Geometry {
+            rows,
+            columns,
+            missing_front,
+            missing_back,
+        })
+    }
+
+    fn row_bits(&self) -> u32 {
+        bits(self.rows)
+    }
+
+    fn column_bits(&self) -> u32 {
+        bits(self.columns)
+    }
+
+    /// Whether the seat exists, its row not being one of the missing ones.
+    fn has_seat(&self, pass: &BoardingPass) -> bool {
+        (self.missing_front..self.rows - self.missing_back).contains(&pass.row)
+            && pass.column < self.columns
+    }
+
+    fn decode(&self, pass: &str) -> Result<BoardingPass, String> {
+        let row_bits = self.row_bits() as usize;
+        let length = row_bits + self.column_bits() as usize;
+        if pass.chars().count() != length {
+            return Err(format!("'{}' is not {} characters long", pass, length));
+        }
+        let mut row = 0;
+        let mut column = 0;
+        for (i, c) in pass.chars().enumerate() {
+            match (i < row_bits, c) {
+                (true, 'F') => row <<= 1,
+                (true, 'B') => row = row << 1 | 1,
+                (false, 'L') => column <<= 1,
+                (false, 'R') => column = column << 1 | 1,
+                (in_row, _) => {
+                    return Err(format!(
+                        "'{}' has '{}' at position {}, expected {}",
+                        pass,
+                        c,
+                        i + 1,
+                        if in_row { "F or B" } else { "L or R" }
+                    ))
+                }
+            }
+        }
+        let decoded = BoardingPass { row, column };
+        if !self.has_seat(&decoded) {
+            return Err(format!(
+                "'{}' is row {} column {}, which is not in the plane",
+                pass, row, column
+            ));
+        }
+        Ok(decoded)
+    }
+
+    /// Encodes the pass back into its `FBLR` form.
+    fn encode(&self, pass: &BoardingPass) -> String {
+        letters(pass.row, self.row_bits(), 'F', 'B')
+            .chain(letters(pass.column, self.column_bits(), 'L', 'R'))
+            .collect()
+    }
+
+    fn seat_id(&self, pass: &BoardingPass) -> u32 {
+        pass.row * self.columns + pass.column
+    }
+
+    fn seat(&self, seat_id: u32) -> Result<BoardingPass, String> {
+        let pass = BoardingPass {
+            row: seat_id / self.columns,
+            column: seat_id % self.columns,
+        };
+        if self.has_seat(&pass) {
+            Ok(pass)
+        } else {
+            Err(format!("there is no seat {} in the plane", seat_id))
+        }
+    }
+}
+
+impl Default for Geometry {
+    fn default() -> Geometry {
+        Geometry::new(128, 8, 0, 0).unwrap()
+    }
+}
+
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    args.remove(i);
+    if i < args.len() {
+        Some(args.remove(i))
+    } else {
+        None
+    }
+}
+
+/// Reads the `--rows`, `--columns`, `--missing-front` and `--missing-back`
+/// options, defaulting to 128 rows of 8 columns.
+fn parse_geometry(args: &mut Vec<String>) -> Result<Geometry, String> {
+    let mut option = |name: &str, default: u32| match take_option(args, name) {
+        Some(value) => value
+            .parse()
+            .map_err(|e| format!("invalid {} '{}': {}", name, value, e)),
+        None => Ok(default),
+    };
+    let rows = option("--rows", 128)?;
+    let columns = option("--columns", 8)?;
+    let missing_front = option("--missing-front", 0)?;
+    let missing_back = option("--missing-back", 0)?;
+    Geometry::new(rows, columns, missing_front, missing_back)
+}
+
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let geometry = match parse_geometry(&mut args) {
+        Ok(geometry) => geometry,
+        Err(message) => {
+            eprintln!("{}", message);
+            return;
+        }
+    };
+    if args.first().map(String::as_str) == Some("encode") {
+        for seat_id in &args[1..] {
+            match seat_id
+                .parse()
+                .map_err(|e| format!("invalid seat id '{}': {}", seat_id, e))
+                .and_then(|seat_id| geometry.seat(seat_id))
+            {
+                Ok(pass) => println!("{}", geometry.encode(&pass)),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+        return;
+    }
+
     let stdin = io::stdin();
 
-    let seat_ids: Vec<u32> = stdin
-        .lock()
-        .lines()
-        .filter_map(Result::ok)
-        .map(|l| {
-            let mut n: u32 = 0;
-            l.chars().for_each(|c| {
-                n = n << 1;
-                match c {
-                    'B' => n = n + 1,
-                    'R' => n = n + 1,
-                    _ => (),
-                }
-            });
-            n
-        })
-        .collect();
+    let mut passes: Vec<BoardingPass> = vec![];
+    for (i, line) in stdin.lock().lines().map_while(Result::ok).enumerate() {
+        match geometry.decode(&line) {
+            Ok(pass) => passes.push(pass),
+            Err(message) => {
+                eprintln!("line {}: {}", i + 1, message);
+                return;
+            }
+        }
+    }
+    let cabin = Cabin::new(&geometry, &passes);
+
+    match args.first().map(String::as_str) {
+        None => {
+            let max_seat_id = passes.iter().map(|p| geometry.seat_id(p)).max();
+            match max_seat_id {
+                Some(max_seat_id) => println!("max seat id: {}", max_seat_id),
+                None => println!("no boarding passes"),
+            }
+
+            match cabin.my_seat() {
+                Ok(my_seat_id) => println!("my seat id: {}", my_seat_id),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+        Some("map") => {
+            if cabin.render(io::stdout().lock()).is_err() {
+                return;
+            }
+            println!("empty seats: {}", format_ids(&cabin.empty_seats()));
+            println!("candidates: {}", format_ids(&cabin.candidates()));
+        }
+        _ => println!("unkown mode"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let max_seat_id = seat_ids.iter().max().unwrap();
+    #[test]
+    fn decode() {
+        let geometry = Geometry::default();
+        for &(pass, row, column, seat_id) in &[
+            ("FBFBBFFRLR", 44, 5, 357),
+            ("BFFFBBFRRR", 70, 7, 567),
+            ("FFFBBBFRRR", 14, 7, 119),
+            ("BBFFBBFRLL", 102, 4, 820),
+        ] {
+            let decoded = geometry.decode(pass).unwrap();
+            assert_eq!(decoded, BoardingPass { row, column });
+            assert_eq!(geometry.seat_id(&decoded), seat_id);
+        }
+    }
 
-    println!("max seat id: {}", max_seat_id);
+    #[test]
+    fn round_trip() {
+        let geometry = Geometry::default();
+        for seat_id in 0..1024 {
+            let pass = geometry.seat(seat_id).unwrap();
+            assert_eq!(geometry.seat_id(&pass), seat_id);
+            let decoded = geometry.decode(&geometry.encode(&pass)).unwrap();
+            assert_eq!(decoded, pass);
+        }
+        assert!(geometry.seat(1024).is_err());
+    }
 
-    let min_seat_id = seat_ids.iter().min().unwrap();
-    let from_min_to_max_sum = ((max_seat_id - min_seat_id + 1) * (max_seat_id + min_seat_id)) / 2;
-    let seat_ids_sum = seat_ids.iter().fold(0, |acc, id| acc + id);
+    #[test]
+    fn invalid() {
+        let geometry = Geometry::default();
+        assert!(geometry.decode("FBFBBFFRL").is_err());
+        assert!(geometry.decode("FBFBBFFRLRR").is_err());
+        assert!(geometry.decode("FBFBBFLRLR").is_err());
+        assert!(geometry.decode("FBFBBFFRLB").is_err());
+        assert!(geometry.decode("fbfbbffrlr").is_err());
+    }
 
-    let my_seat_id = from_min_to_max_sum - seat_ids_sum;
+    #[test]
+    fn configured() {
+        // 5 letters for 20 rows, 3 letters for 6 columns, rows 0 and 19 missing.
+        let geometry = Geometry::new(20, 6, 1, 1).unwrap();
+        let pass = geometry.decode("FFFFBLRR").unwrap();
+        assert_eq!(pass, BoardingPass { row: 1, column: 3 });
+        assert_eq!(geometry.seat_id(&pass), 9);
+        assert_eq!(geometry.encode(&geometry.seat(9).unwrap()), "FFFFBLRR");
+        assert!(geometry.decode("FFFFFLRR").is_err());
+        assert!(geometry.decode("BFFBBLRR").is_err());
+        assert!(geometry.decode("FFFFBRRL").is_err());
+        assert!(geometry.decode("BFFFFFFLRR").is_err());
+        assert!(geometry.seat(5).is_err());
+        assert!(geometry.seat(18 * 6 + 5).is_ok());
+        assert!(geometry.seat(19 * 6).is_err());
 
-    println!("my seat id: {}", my_seat_id);
+        assert!(Geometry::new(0, 8, 0, 0).is_err());
+        assert!(Geometry::new(4, 8, 2, 2).is_err());
+        assert!(Geometry::new(4, 8, u32::MAX, 1).is_err());
+        assert!(Geometry::new(3_000_000_000, 3, 0, 0).is_err());
+        assert!(Geometry::new(65536, 65536, 0, 0).is_err());
+        let geometry = Geometry::new(65536, 65535, 0, 0).unwrap();
+        let last = geometry.seat(u32::MAX - 65536).unwrap();
+        assert_eq!(geometry.seat_id(&last), u32::MAX - 65536);
+    }
 }
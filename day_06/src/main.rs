@@ -1,88 +1,244 @@
-use std::collections::HashSet;
+use stats::Stats;
 use std::env;
 use std::io;
 use std::io::prelude::*;
 
+mod stats;
+
+/// The questions a person answered "yes" to, bit 0 for `a` up to bit 25 for `z`.
+type Answers = u32;
+
+/// Parses the answers of one person, each question `a` to `z` at most once.
+fn answers(line: &str) -> Result<Answers, String> {
+    let mut answers = 0;
+    for (i, c) in line.chars().enumerate() {
+        if !c.is_ascii_lowercase() {
+            return Err(format!(
+                "'{}' at column {} is not a question a to z",
+                c,
+                i + 1
+            ));
+        }
+        let question = 1 << (c as u8 - b'a');
+        if answers & question != 0 {
+            return Err(format!("'{}' at column {} is answered twice", c, i + 1));
+        }
+        answers |= question;
+    }
+    Ok(answers)
+}
+
+/// Groups of people's answers, one person per line, groups being separated
+/// by one or more blank lines. Groups are never empty.
 struct GroupAnswersStream<R: BufRead> {
     lines: std::io::Lines<R>,
+    /// Lines read so far.
+    line: usize,
     done: bool,
 }
 impl<R: BufRead> GroupAnswersStream<R> {
     fn new(reader: R) -> GroupAnswersStream<R> {
         GroupAnswersStream {
             lines: reader.lines(),
+            line: 0,
             done: false,
         }
     }
 }
 impl<R: BufRead> Iterator for GroupAnswersStream<R> {
-    type Item = Vec<Vec<char>>;
+    type Item = Result<Vec<Answers>, String>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.done {
-            return None;
-        }
-        let mut group: Self::Item = vec![];
+        let mut group: Vec<Answers> = vec![];
 
-        loop {
+        while !self.done {
             let line = match self.lines.next() {
                 None => {
                     self.done = true;
                     break;
                 }
-                Some(Err(_)) => panic!("error"),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(format!("line {}: {}", self.line + 1, e)));
+                }
                 Some(Ok(l)) => l,
             };
+            self.line += 1;
 
-            match line.as_str() {
+            match line.trim_end() {
+                "" if group.is_empty() => continue,
                 "" => break,
-                _ => {
-                    group.push(line.chars().collect());
-                }
+                line => match answers(line) {
+                    Ok(answers) => group.push(answers),
+                    Err(message) => return Some(Err(format!("line {}: {}", self.line, message))),
+                },
             }
         }
 
-        Some(group)
+        if group.is_empty() {
+            None
+        } else {
+            Some(Ok(group))
+        }
     }
 }
 
+/// How the answers of the people of a group combine into the group's questions.
+#[derive(Debug, PartialEq)]
+enum Operation {
+    /// Answered by anyone.
+    Union,
+    /// Answered by everyone.
+    Intersection,
+    /// Answered by an odd number of people.
+    SymmetricDifference,
+    AtLeast(usize),
+    Exactly(usize),
+}
+impl Operation {
+    /// Parses `union`, `intersection`, `xor`, `at-least=K` or `exactly=K`.
+    fn parse(input: &str) -> Result<Operation, String> {
+        let (name, argument) = match input.split_once('=') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (input, None),
+        };
+        let k = || -> Result<usize, String> {
+            argument
+                .ok_or_else(|| format!("'{}' needs a number of people like '{}=2'", name, name))?
+                .parse()
+                .map_err(|e| format!("invalid number of people in '{}': {}", input, e))
+        };
+        match (name, argument) {
+            ("union", None) => Ok(Operation::Union),
+            ("intersection", None) => Ok(Operation::Intersection),
+            ("xor", None) => Ok(Operation::SymmetricDifference),
+            ("at-least", _) => Ok(Operation::AtLeast(k()?)),
+            ("exactly", _) => Ok(Operation::Exactly(k()?)),
+            _ => Err(format!(
+                "unkown operation '{}', expected union, intersection, xor, at-least=K or exactly=K",
+                input
+            )),
+        }
+    }
+
+    fn evaluate(&self, group: &[Answers]) -> Answers {
+        match self {
+            Operation::Union => group.iter().fold(0, |all, a| all | a),
+            Operation::Intersection if group.is_empty() => 0,
+            Operation::Intersection => group.iter().fold(!0, |all, a| all & a),
+            Operation::SymmetricDifference => group.iter().fold(0, |all, a| all ^ a),
+            Operation::AtLeast(k) => by_people(group, |people| people >= *k),
+            Operation::Exactly(k) => by_people(group, |people| people == *k),
+        }
+    }
+}
+
+/// The questions for which the number of people answering them passes `test`.
+fn by_people<F: Fn(usize) -> bool>(group: &[Answers], test: F) -> Answers {
+    (0..26)
+        .filter(|&question| {
+            let people = group.iter().filter(|a| *a >> question & 1 == 1).count();
+            test(people)
+        })
+        .fold(0, |all, question| all | 1 << question)
+}
+
+fn sum_of_counts<R: BufRead>(reader: R, operation: &Operation) -> Result<u32, String> {
+    let mut sum = 0;
+    for group in GroupAnswersStream::new(reader) {
+        sum += operation.evaluate(&group?).count_ones();
+    }
+    Ok(sum)
+}
+
 fn main() {
     let stdin = io::stdin();
 
-    let part: String = env::args().nth(1).unwrap();
-    match part.as_str() {
-        "part_1" => {
-            let stream = GroupAnswersStream::new(stdin.lock());
-            let res = stream
-                .map(|g| {
-                    let t: HashSet<char> = g.into_iter().flatten().collect();
-                    t.len()
-                })
-                .fold(0, |acc, count| acc + count);
-            println!("{}", res);
-        }
-        "part_2" => {
-            let stream = GroupAnswersStream::new(stdin.lock());
-            let res = stream
-                .map(|g| {
-                    g.iter()
-                        .fold(
-                            g.first()
-                                .unwrap()
-                                .iter()
-                                .cloned()
-                                .collect::<HashSet<char>>(),
-                            |memo, answers| {
-                                let answers_set =
-                                    answers.iter().cloned().collect::<HashSet<char>>();
-                                memo.intersection(&answers_set).cloned().collect()
-                            },
-                        )
-                        .len()
-                })
-                .fold(0, |acc, count| acc + count);
-            println!("{}", res);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("stats") {
+        let format = args.get(1).map_or("table", String::as_str);
+        match stats::Format::parse(format, args.get(2).map(String::as_str)) {
+            Ok(format) => match Stats::new(GroupAnswersStream::new(stdin.lock())) {
+                Ok(stats) => {
+                    if let Err(e) = stats.write(format, io::stdout().lock()) {
+                        eprintln!("{}", e);
+                    }
+                }
+                Err(message) => eprintln!("{}", message),
+            },
+            Err(message) => eprintln!("{}", message),
         }
-        _ => (),
+        return;
+    }
+    let operation = match args.first().map(String::as_str) {
+        Some("part_1") => Ok(Operation::Union),
+        Some("part_2") => Ok(Operation::Intersection),
+        Some("eval") => match args.get(1) {
+            Some(operation) => Operation::parse(operation),
+            None => Err(String::from("eval needs an operation")),
+        },
+        _ => return,
+    };
+    match operation.and_then(|operation| sum_of_counts(stdin.lock(), &operation)) {
+        Ok(sum) => println!("{}", sum),
+        Err(message) => eprintln!("{}", message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let example = &include_bytes!("../example_part_1.txt")[..];
+        assert_eq!(sum_of_counts(example, &Operation::Union), Ok(11));
+        assert_eq!(sum_of_counts(example, &Operation::Intersection), Ok(6));
+        assert_eq!(sum_of_counts(example, &Operation::AtLeast(1)), Ok(11));
+    }
+
+    #[test]
+    fn operations() {
+        let group = [
+            answers("abc").unwrap(),
+            answers("abd").unwrap(),
+            answers("ae").unwrap(),
+        ];
+        let evaluate = |operation: &str| {
+            let questions = Operation::parse(operation).unwrap().evaluate(&group);
+            (0..26u8)
+                .filter(|q| questions >> q & 1 == 1)
+                .map(|q| (b'a' + q) as char)
+                .collect::<String>()
+        };
+        assert_eq!(evaluate("union"), "abcde");
+        assert_eq!(evaluate("intersection"), "a");
+        assert_eq!(evaluate("xor"), "acde");
+        assert_eq!(evaluate("at-least=2"), "ab");
+        assert_eq!(evaluate("exactly=1"), "cde");
+        assert_eq!(evaluate("exactly=0"), "fghijklmnopqrstuvwxyz");
+        assert!(Operation::parse("at-least").is_err());
+        assert!(Operation::parse("union=2").is_err());
+        assert!(Operation::parse("exactly=x").is_err());
+    }
+
+    #[test]
+    fn parsing() {
+        let groups: Vec<Result<Vec<Answers>, String>> =
+            GroupAnswersStream::new(&b"\n\nab\nc\n\n\n\nd\r\n\n\n"[..]).collect();
+        assert_eq!(groups, vec![Ok(vec![0b11, 0b100]), Ok(vec![0b1000])]);
+
+        let error = |input: &[u8]| GroupAnswersStream::new(input).find_map(Result::err);
+        assert_eq!(
+            error(b"ab\n\nabca\n"),
+            Some(String::from("line 3: 'a' at column 4 is answered twice"))
+        );
+        assert_eq!(
+            error(b"ab\na B\n"),
+            Some(String::from(
+                "line 2: ' ' at column 2 is not a question a to z"
+            ))
+        );
+        assert_eq!(GroupAnswersStream::new(&b"\n\n"[..]).count(), 0);
     }
 }
@@ -0,0 +1,192 @@
+use crate::{Answers, Operation};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::prelude::*;
+
+/// One of the tables of the statistics, CSV output having one table only.
+pub enum Section {
+    Questions,
+    Sizes,
+    Groups,
+}
+impl Section {
+    fn parse(name: &str) -> Result<Section, String> {
+        match name {
+            "questions" => Ok(Section::Questions),
+            "sizes" => Ok(Section::Sizes),
+            "groups" => Ok(Section::Groups),
+            _ => Err(format!(
+                "unkown section '{}', expected questions, sizes or groups",
+                name
+            )),
+        }
+    }
+}
+
+pub enum Format {
+    /// All the sections, one after another.
+    Table,
+    Csv(Section),
+}
+impl Format {
+    /// Parses `table` or `csv`, the latter followed by the section, by
+    /// default `questions`.
+    pub fn parse(name: &str, section: Option<&str>) -> Result<Format, String> {
+        match (name, section) {
+            ("table", None) => Ok(Format::Table),
+            ("table", Some(section)) => Err(format!(
+                "the table format has every section, got '{}'",
+                section
+            )),
+            ("csv", section) => Ok(Format::Csv(Section::parse(section.unwrap_or("questions"))?)),
+            _ => Err(format!("unkown format '{}', expected table or csv", name)),
+        }
+    }
+}
+
+fn letter(question: usize) -> char {
+    (b'a' + question as u8) as char
+}
+
+fn letters(questions: Answers) -> String {
+    (0..26)
+        .filter(|q| questions >> q & 1 == 1)
+        .map(letter)
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Stats {
+    /// How many people answered each question.
+    pub people: [usize; 26],
+    /// How many groups had someone answering each question.
+    pub groups: [usize; 26],
+    /// How many groups had everyone answering each question.
+    pub unanimous: [usize; 26],
+    /// How many groups have each number of people.
+    pub sizes: BTreeMap<usize, usize>,
+    /// The questions everyone answered in each group, in input order.
+    pub unanimous_by_group: Vec<Answers>,
+}
+impl Stats {
+    pub fn new<I: Iterator<Item = Result<Vec<Answers>, String>>>(
+        groups: I,
+    ) -> Result<Stats, String> {
+        let mut stats: Stats = Default::default();
+        for group in groups {
+            let group = group?;
+            let anyone = Operation::Union.evaluate(&group);
+            let everyone = Operation::Intersection.evaluate(&group);
+            for question in 0..26 {
+                stats.people[question] += group.iter().filter(|a| *a >> question & 1 == 1).count();
+                stats.groups[question] += (anyone >> question & 1) as usize;
+                stats.unanimous[question] += (everyone >> question & 1) as usize;
+            }
+            *stats.sizes.entry(group.len()).or_insert(0) += 1;
+            stats.unanimous_by_group.push(everyone);
+        }
+        Ok(stats)
+    }
+
+    pub fn write<W: Write>(&self, format: Format, mut out: W) -> io::Result<()> {
+        match format {
+            Format::Table => {
+                writeln!(out, "question  people  groups  unanimous")?;
+                for question in 0..26 {
+                    writeln!(
+                        out,
+                        "{:<8}  {:>6}  {:>6}  {:>9}",
+                        letter(question),
+                        self.people[question],
+                        self.groups[question],
+                        self.unanimous[question]
+                    )?;
+                }
+                writeln!(out, "\ngroup size  groups")?;
+                for (size, groups) in &self.sizes {
+                    writeln!(out, "{:>10}  {:>6}", size, groups)?;
+                }
+                writeln!(out, "\ngroup  unanimous questions")?;
+                for (i, questions) in self.unanimous_by_group.iter().enumerate() {
+                    let line = format!("{:>5}  {}", i + 1, letters(*questions));
+                    writeln!(out, "{}", line.trim_end())?;
+                }
+            }
+            Format::Csv(Section::Questions) => {
+                writeln!(out, "question,people,groups,unanimous")?;
+                for question in 0..26 {
+                    writeln!(
+                        out,
+                        "{},{},{},{}",
+                        letter(question),
+                        self.people[question],
+                        self.groups[question],
+                        self.unanimous[question]
+                    )?;
+                }
+            }
+            Format::Csv(Section::Sizes) => {
+                writeln!(out, "group_size,groups")?;
+                for (size, groups) in &self.sizes {
+                    writeln!(out, "{},{}", size, groups)?;
+                }
+            }
+            Format::Csv(Section::Groups) => {
+                writeln!(out, "group,unanimous_questions")?;
+                for (i, questions) in self.unanimous_by_group.iter().enumerate() {
+                    writeln!(out, "{},{}", i + 1, letters(*questions))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GroupAnswersStream;
+
+    #[test]
+    fn example() {
+        let stats = Stats::new(GroupAnswersStream::new(
+            &include_bytes!("../example_part_1.txt")[..],
+        ))
+        .unwrap();
+        assert_eq!(&stats.people[..3], &[8, 4, 3]);
+        assert_eq!(&stats.groups[..3], &[4, 4, 3]);
+        assert_eq!(&stats.unanimous[..3], &[3, 2, 1]);
+        assert_eq!(
+            stats
+                .sizes
+                .iter()
+                .map(|(&k, &v)| (k, v))
+                .collect::<Vec<_>>(),
+            vec![(1, 2), (2, 1), (3, 1), (4, 1)]
+        );
+        let unanimous: Vec<String> = stats
+            .unanimous_by_group
+            .iter()
+            .map(|q| letters(*q))
+            .collect();
+        assert_eq!(unanimous, vec!["abc", "", "a", "a", "b"]);
+
+        let csv = |section: &str| {
+            let mut out = vec![];
+            stats
+                .write(Format::parse("csv", Some(section)).unwrap(), &mut out)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let questions = csv("questions");
+        assert_eq!(questions.lines().count(), 27);
+        assert!(questions.starts_with("question,people,groups,unanimous\na,8,4,3\nb,4,4,2\n"));
+        assert_eq!(csv("sizes"), "group_size,groups\n1,2\n2,1\n3,1\n4,1\n");
+        assert_eq!(
+            csv("groups"),
+            "group,unanimous_questions\n1,abc\n2,\n3,a\n4,a\n5,b\n"
+        );
+        assert!(Format::parse("csv", Some("people")).is_err());
+        assert!(Format::parse("table", Some("sizes")).is_err());
+    }
+}
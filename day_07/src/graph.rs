@@ -0,0 +1,247 @@
+use crate::{BagRule, ContainsRule, Counter};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// The bag rules both ways: what each colour contains, and which colours
+/// directly contain it.
+pub struct Bags {
+    contents: HashMap<String, Vec<ContainsRule>>,
+    containers: HashMap<String, Vec<String>>,
+}
+impl Bags {
+    /// Keeps the first rule of colours having several.
+    pub fn new(rules: Vec<BagRule>) -> Bags {
+        let mut contents: HashMap<String, Vec<ContainsRule>> = HashMap::new();
+        let mut containers: HashMap<String, Vec<String>> = HashMap::new();
+        for rule in rules {
+            if contents.contains_key(&rule.name) {
+                continue;
+            }
+            for contains_rule in &rule.contains_rules {
+                containers
+                    .entry(contains_rule.name.clone())
+                    .or_default()
+                    .push(rule.name.clone());
+            }
+            contents.insert(rule.name, rule.contains_rules);
+        }
+        Bags {
+            contents,
+            containers,
+        }
+    }
+
+    /// `color` as stored in the rules.
+    fn known(&self, color: &str) -> Result<&str, String> {
+        match self.contents.get_key_value(color) {
+            Some((color, _)) => Ok(color),
+            None => Err(format!("no rule for {} bags", color)),
+        }
+    }
+
+    /// The colours that eventually contain `color`, with how many bags deep
+    /// `color` is in them at least, closest first.
+    pub fn containers_of(&self, color: &str) -> Result<Vec<(usize, &str)>, String> {
+        let color = self.known(color)?;
+        let mut found: Vec<(usize, &str)> = vec![];
+        let mut seen: HashSet<&str> = HashSet::new();
+        seen.insert(color);
+        let mut queue: VecDeque<(usize, &str)> = VecDeque::new();
+        queue.push_back((0, color));
+        while let Some((depth, color)) = queue.pop_front() {
+            for container in self.containers.get(color).into_iter().flatten() {
+                if seen.insert(container) {
+                    found.push((depth + 1, container));
+                    queue.push_back((depth + 1, container));
+                }
+            }
+        }
+        found.sort();
+        Ok(found)
+    }
+
+    /// How many bags of each colour one `color` bag contains.
+    pub fn contents_of(&self, color: &str) -> Result<BTreeMap<&str, u128>, String> {
+        let color = self.known(color)?;
+        // Counting first reports cycles, missing rules and overflows, the
+        // count of each colour inside then fitting as it is at most the total.
+        let mut counter = Counter::new(&self.contents);
+        counter.count(color).map_err(|e| e.to_string())?;
+
+        let mut counts: HashMap<&str, u128> = HashMap::new();
+        counts.insert(color, 1);
+        for &outer in counter.order.iter().rev() {
+            let outer_count = counts[outer];
+            for rule in &self.contents[outer] {
+                *counts.entry(&rule.name).or_insert(0) += outer_count * rule.number as u128;
+            }
+        }
+        counts.remove(color);
+        Ok(counts.into_iter().collect())
+    }
+
+    /// The shortest chain of bags from `from` down to `to`, each colour with
+    /// how many of it the previous one contains.
+    pub fn path(&self, from: &str, to: &str) -> Result<Vec<(usize, &str)>, String> {
+        let from = self.known(from)?;
+        let to = self.known(to)?;
+        let mut previous: HashMap<&str, (usize, &str)> = HashMap::new();
+        let mut queue: VecDeque<&str> = VecDeque::new();
+        queue.push_back(from);
+        while let Some(color) = queue.pop_front() {
+            if color == to {
+                let mut path = vec![(1, color)];
+                let mut color = color;
+                while let Some(&(number, outer)) = previous.get(color) {
+                    path.last_mut().unwrap().0 = number;
+                    path.push((1, outer));
+                    color = outer;
+                }
+                path.reverse();
+                return Ok(path);
+            }
+            for rule in self.contents.get(color).into_iter().flatten() {
+                let inner = rule.name.as_str();
+                if inner != from && !previous.contains_key(inner) {
+                    previous.insert(inner, (rule.number, color));
+                    queue.push_back(inner);
+                }
+            }
+        }
+        Err(format!("{} bags cannot contain {} bags", from, to))
+    }
+
+    /// The colours no other bag contains.
+    pub fn roots(&self) -> Vec<&str> {
+        let mut roots: Vec<&str> = self
+            .contents
+            .keys()
+            .filter(|color| !self.containers.contains_key(*color))
+            .map(String::as_str)
+            .collect();
+        roots.sort_unstable();
+        roots
+    }
+
+    /// The colours containing no other bags.
+    pub fn leaves(&self) -> Vec<&str> {
+        let mut leaves: Vec<&str> = self
+            .contents
+            .iter()
+            .filter(|(_, contains_rules)| contains_rules.is_empty())
+            .map(|(color, _)| color.as_str())
+            .collect();
+        leaves.sort_unstable();
+        leaves
+    }
+}
+
+/// How many `to` bags are inside one `from` bag along `path`.
+pub fn multiplier(path: &[(usize, &str)]) -> Option<u128> {
+    path.iter()
+        .skip(1)
+        .try_fold(1u128, |product, &(number, _)| {
+            product.checked_mul(number as u128)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, contains: &[(usize, &str)]) -> BagRule {
+        BagRule {
+            name: String::from(name),
+            contains_rules: contains
+                .iter()
+                .map(|&(number, name)| ContainsRule {
+                    number,
+                    name: String::from(name),
+                })
+                .collect(),
+        }
+    }
+
+    fn example() -> Bags {
+        Bags::new(vec![
+            rule("light red", &[(1, "bright white"), (2, "muted yellow")]),
+            rule("dark orange", &[(3, "bright white"), (4, "muted yellow")]),
+            rule("bright white", &[(1, "shiny gold")]),
+            rule("muted yellow", &[(2, "shiny gold"), (9, "faded blue")]),
+            rule("shiny gold", &[(1, "dark olive"), (2, "vibrant plum")]),
+            rule("dark olive", &[(3, "faded blue"), (4, "dotted black")]),
+            rule("vibrant plum", &[(5, "faded blue"), (6, "dotted black")]),
+            rule("faded blue", &[]),
+            rule("dotted black", &[]),
+        ])
+    }
+
+    #[test]
+    fn queries() {
+        let bags = example();
+        assert_eq!(
+            bags.containers_of("shiny gold").unwrap(),
+            vec![
+                (1, "bright white"),
+                (1, "muted yellow"),
+                (2, "dark orange"),
+                (2, "light red")
+            ]
+        );
+
+        let contents = bags.contents_of("shiny gold").unwrap();
+        assert_eq!(contents.values().sum::<u128>(), 32);
+        assert_eq!(contents["faded blue"], 13);
+        assert_eq!(contents["dotted black"], 16);
+
+        let path = bags.path("light red", "faded blue").unwrap();
+        assert_eq!(
+            path,
+            vec![(1, "light red"), (2, "muted yellow"), (9, "faded blue")]
+        );
+        assert_eq!(multiplier(&path), Some(18));
+        assert!(bags.path("faded blue", "light red").is_err());
+
+        assert_eq!(bags.roots(), vec!["dark orange", "light red"]);
+        assert_eq!(bags.leaves(), vec!["dotted black", "faded blue"]);
+
+        assert_eq!(
+            bags.containers_of("mauve").unwrap_err(),
+            "no rule for mauve bags"
+        );
+        assert!(bags.contents_of("mauve").is_err());
+        assert!(bags.path("light red", "mauve").is_err());
+    }
+
+    #[test]
+    fn cycle() {
+        let bags = Bags::new(vec![
+            rule("shiny gold", &[(1, "dark red")]),
+            rule("dark red", &[(2, "shiny gold")]),
+            rule("light red", &[(1, "shiny gold")]),
+        ]);
+        assert_eq!(
+            bags.containers_of("shiny gold").unwrap(),
+            vec![(1, "dark red"), (1, "light red")]
+        );
+        assert_eq!(
+            bags.containers_of("dark red").unwrap(),
+            vec![(1, "shiny gold"), (2, "light red")]
+        );
+        assert!(bags.contents_of("shiny gold").is_err());
+    }
+
+    #[test]
+    fn deep() {
+        let names: Vec<String> = (0..=100_000).map(|i| format!("bag {}", i)).collect();
+        let mut rules: Vec<BagRule> = names
+            .windows(2)
+            .map(|pair| rule(&pair[0], &[(1, &pair[1])]))
+            .collect();
+        rules.push(rule(&names[100_000], &[]));
+        let bags = Bags::new(rules);
+        let contents = bags.contents_of("bag 0").unwrap();
+        assert_eq!(contents.len(), 100_000);
+        assert_eq!(contents["bag 100000"], 1);
+        assert_eq!(bags.containers_of("bag 100000").unwrap().len(), 100_000);
+    }
+}
@@ -1,10 +1,17 @@
+use graph::{multiplier, Bags};
 use regex::Regex;
+use std::fmt;
+use std::io;
 use std::io::prelude::*;
-use std::{collections::HashMap, env};
-use std::{collections::HashSet, io};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
 #[macro_use]
 extern crate lazy_static;
 
+mod graph;
+
 #[derive(Debug)]
 struct ContainsRule {
     number: usize,
@@ -19,12 +26,12 @@ struct BagRule {
 
 fn main() {
     let stdin = io::stdin();
-    let part: String = env::args().nth(1).unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
 
     let rules: Vec<_> = stdin
         .lock()
         .lines()
-        .filter_map(Result::ok)
+        .map_while(Result::ok)
         .map(|l| {
             lazy_static! {
                 static ref RE: Regex = Regex::new(r"^(\w+ \w+) bags contain (.*).$").unwrap();
@@ -50,7 +57,7 @@ fn main() {
                                     let name = caps2.get(2).unwrap().as_str();
                                     ContainsRule {
                                         name: String::from(name),
-                                        number: number,
+                                        number,
                                     }
                                 })
                                 .collect::<Vec<_>>()
@@ -62,57 +69,257 @@ fn main() {
         })
         .collect();
 
-    match part.as_str() {
-        "part_1" => {
-            let mut contained_vertices: HashMap<String, Vec<String>> = HashMap::new();
-            for rule in rules {
-                for contains_rule in rule.contains_rules {
-                    let colors = contained_vertices
-                        .entry(contains_rule.name.clone())
-                        .or_insert(vec![]);
-                    colors.push(rule.name.clone());
-                }
-            }
+    let bags = Bags::new(rules);
+    if let Err(message) = query(&bags, &args) {
+        eprintln!("{}", message);
+    }
+}
 
-            let mut colors: HashSet<&str> = HashSet::new();
-            let mut queue = vec!["shiny gold"];
+/// Pairs up the words of `args` into `count` two-word colours, whether or
+/// not each colour was quoted as one argument.
+fn colors(args: &[String], count: usize) -> Result<Vec<String>, String> {
+    let words: Vec<&str> = args.iter().flat_map(|a| a.split_whitespace()).collect();
+    if words.len() != 2 * count {
+        return Err(format!(
+            "expected {} colour{} like 'shiny gold', got '{}'",
+            count,
+            if count == 1 { "" } else { "s" },
+            words.join(" ")
+        ));
+    }
+    Ok(words.chunks(2).map(|color| color.join(" ")).collect())
+}
 
-            while let Some(color) = queue.pop() {
-                colors.insert(color);
-                if let Some(container_colors) = contained_vertices.get(color) {
-                    for container_color in container_colors {
-                        if !colors.contains(container_color.as_str()) {
-                            queue.push(container_color.as_str())
-                        }
+fn query(bags: &Bags, args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("part_1") => println!("{:?}", bags.containers_of("shiny gold")?.len()),
+        Some("part_2") => {
+            let contents = bags.contents_of("shiny gold")?;
+            println!("{:?}", contents.values().sum::<u128>());
+        }
+        Some("containers-of") => {
+            let color = &colors(&args[1..], 1)?[0];
+            let containers = bags.containers_of(color)?;
+            for (depth, container) in &containers {
+                println!("{} {}", depth, container);
+            }
+            println!("total: {}", containers.len());
+        }
+        Some("contents-of") => {
+            let color = &colors(&args[1..], 1)?[0];
+            let mut contents: Vec<(&str, u128)> = bags.contents_of(color)?.into_iter().collect();
+            let total: u128 = contents.iter().map(|(_, count)| count).sum();
+            contents.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            for (inner, count) in contents {
+                println!("{} {}", count, inner);
+            }
+            println!("total: {}", total);
+        }
+        Some("path") => {
+            let colors = colors(&args[1..], 2)?;
+            let path = bags.path(&colors[0], &colors[1])?;
+            let steps: Vec<String> = path
+                .iter()
+                .enumerate()
+                .map(|(i, (number, color))| {
+                    if i == 0 {
+                        String::from(*color)
+                    } else {
+                        format!("{} {}", number, color)
                     }
-                }
+                })
+                .collect();
+            println!("{}", steps.join(" -> "));
+            match multiplier(&path) {
+                Some(multiplier) => println!("multiplier: {}", multiplier),
+                None => return Err(String::from("the multiplier is too large")),
             }
+        }
+        Some("roots") => bags.roots().iter().for_each(|color| println!("{}", color)),
+        Some("leaves") => bags.leaves().iter().for_each(|color| println!("{}", color)),
+        _ => {
+            return Err(String::from(
+                "expected part_1, part_2, containers-of, contents-of, path, roots or leaves",
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum CountError {
+    UnknownColor(String),
+    /// The colours of a bag containing itself, from and back to that bag.
+    Cycle(Vec<String>),
+    /// The colour of the bag whose count does not fit.
+    Overflow(String),
+}
+impl fmt::Display for CountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CountError::UnknownColor(color) => write!(f, "no rule for {} bags", color),
+            CountError::Cycle(path) => write!(f, "bags contain themselves: {}", path.join(" -> ")),
+            CountError::Overflow(color) => write!(f, "too many bags in {} bags", color),
+        }
+    }
+}
+
+/// Counts bags depth first without recursing, remembering the count of each
+/// colour and the colours being counted to notice cycles.
+struct Counter<'a> {
+    env: &'a HashMap<String, Vec<ContainsRule>>,
+    counts: HashMap<&'a str, u128>,
+    /// The colours being counted, outermost first, each with the index of
+    /// the next of its rules to count.
+    path: Vec<(&'a str, usize)>,
+    on_path: HashSet<&'a str>,
+    /// The colours counted so far, each after all the colours inside it.
+    order: Vec<&'a str>,
+}
+impl<'a> Counter<'a> {
+    fn new(env: &'a HashMap<String, Vec<ContainsRule>>) -> Counter<'a> {
+        Counter {
+            env,
+            counts: HashMap::new(),
+            path: vec![],
+            on_path: HashSet::new(),
+            order: vec![],
+        }
+    }
 
-            println!("{:?}", colors.iter().count() - 1);
+    /// The number of bags, `color` included, that make up one `color` bag.
+    fn count(&mut self, color: &'a str) -> Result<u128, CountError> {
+        if let Some(&count) = self.counts.get(color) {
+            return Ok(count);
         }
-        "part_2" => {
-            let mut contains_vertices: HashMap<String, Vec<ContainsRule>> = HashMap::new();
-            for rule in rules {
-                contains_vertices
-                    .entry(rule.name)
-                    .or_insert(rule.contains_rules);
+        self.enter(color)?;
+        let env = self.env;
+        while let Some(&(outer, next)) = self.path.last() {
+            let contains_rules = &env[outer];
+            if let Some(rule) = contains_rules.get(next) {
+                self.path.last_mut().unwrap().1 += 1;
+                if !self.counts.contains_key(rule.name.as_str()) {
+                    self.enter(&rule.name)?;
+                }
+                continue;
             }
 
-            println!("{:#?}", contains_vertices);
+            let mut count: u128 = 1;
+            for rule in contains_rules {
+                count = (rule.number as u128)
+                    .checked_mul(self.counts[rule.name.as_str()])
+                    .and_then(|bags| count.checked_add(bags))
+                    .ok_or_else(|| CountError::Overflow(String::from(outer)))?;
+            }
+            self.path.pop();
+            self.on_path.remove(outer);
+            self.counts.insert(outer, count);
+            self.order.push(outer);
+        }
+        Ok(self.counts[color])
+    }
 
-            println!("{:?}", bags_count(&contains_vertices, "shiny gold") - 1);
+    /// Starts counting `color`, which is inside the last colour of the path.
+    fn enter(&mut self, color: &'a str) -> Result<(), CountError> {
+        if self.on_path.contains(color) {
+            let start = self.path.iter().position(|&(c, _)| c == color).unwrap();
+            let mut cycle: Vec<String> = self.path[start..]
+                .iter()
+                .map(|&(c, _)| String::from(c))
+                .collect();
+            cycle.push(String::from(color));
+            return Err(CountError::Cycle(cycle));
         }
-        _ => (),
+        if !self.env.contains_key(color) {
+            return Err(CountError::UnknownColor(String::from(color)));
+        }
+        self.path.push((color, 0));
+        self.on_path.insert(color);
+        Ok(())
     }
 }
 
-fn bags_count(env: &HashMap<String, Vec<ContainsRule>>, color: &str) -> usize {
-    let contains_rules = env.get(&String::from(color)).unwrap();
-    contains_rules.iter().fold(1, |acc, rule| {
-        acc + rule.number * bags_count(env, &rule.name)
-    })
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-// bags_count("shiny gold")
-// = 1 + 2 * bags_count("dark red")
-// = 1 + 2 * (2 * bags_count("dark orange"))
+    fn env(rules: &[(&str, &[(usize, &str)])]) -> HashMap<String, Vec<ContainsRule>> {
+        rules
+            .iter()
+            .map(|(color, contains)| {
+                let contains_rules = contains
+                    .iter()
+                    .map(|&(number, name)| ContainsRule {
+                        number,
+                        name: String::from(name),
+                    })
+                    .collect();
+                (String::from(*color), contains_rules)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn count() {
+        let env = env(&[
+            ("shiny gold", &[(2, "dark red")]),
+            ("dark red", &[(2, "dark orange")]),
+            ("dark orange", &[(2, "dark yellow")]),
+            ("dark yellow", &[]),
+        ]);
+        assert_eq!(Counter::new(&env).count("shiny gold"), Ok(15));
+        assert_eq!(
+            Counter::new(&env).count("faded blue"),
+            Err(CountError::UnknownColor(String::from("faded blue")))
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let env = env(&[
+            ("shiny gold", &[(1, "dark red")]),
+            ("dark red", &[(2, "dark orange")]),
+            ("dark orange", &[(1, "dark red")]),
+        ]);
+        let cycle = vec!["dark red", "dark orange", "dark red"];
+        assert_eq!(
+            Counter::new(&env).count("shiny gold"),
+            Err(CountError::Cycle(
+                cycle.into_iter().map(String::from).collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let mut env: HashMap<String, Vec<ContainsRule>> = (0..40)
+            .map(|i| {
+                let contains_rules = vec![ContainsRule {
+                    number: 1000,
+                    name: format!("bag {}", i + 1),
+                }];
+                (format!("bag {}", i), contains_rules)
+            })
+            .collect();
+        env.insert(String::from("bag 40"), vec![]);
+        assert_eq!(
+            Counter::new(&env).count("bag 0"),
+            Err(CountError::Overflow(String::from("bag 27")))
+        );
+    }
+
+    #[test]
+    fn deep() {
+        let mut env: HashMap<String, Vec<ContainsRule>> = (0..100_000)
+            .map(|i| {
+                let contains_rules = vec![ContainsRule {
+                    number: 1,
+                    name: format!("bag {}", i + 1),
+                }];
+                (format!("bag {}", i), contains_rules)
+            })
+            .collect();
+        env.insert(String::from("bag 100000"), vec![]);
+        assert_eq!(Counter::new(&env).count("bag 0"), Ok(100_001));
+    }
+}